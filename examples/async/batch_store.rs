@@ -0,0 +1,46 @@
+use std::env;
+use tinify::prelude::*;
+use tokio::task::JoinSet;
+
+// TINIFY_KEY=.. AWS_ACCESS_KEY_ID=.. AWS_SECRET_ACCESS_KEY=.. cargo run --features async --example batch_store
+//
+// Set TINIFY_ENDPOINT, like `http://127.0.0.1:8080`, to run it against a mock server instead of the API.
+#[tokio::main]
+async fn main() -> Result<(), TinifyError> {
+  let key = env::var("TINIFY_KEY").unwrap_or_default();
+  let mut tinify = Tinify::new().set_key(&key).set_global_concurrency(4);
+  if let Ok(endpoint) = env::var("TINIFY_ENDPOINT") {
+    tinify = tinify.set_endpoint(&endpoint)?;
+  }
+  let client = tinify.get_async_client()?;
+  let access_key_id = env::var("AWS_ACCESS_KEY_ID").unwrap_or_default();
+  let secret_access_key = env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default();
+
+  let mut batch = JoinSet::new();
+  for (input, path) in [
+    ("./unoptimized.jpg", "example-bucket/images/optimized.jpg"),
+    ("./unoptimized.png", "example-bucket/images/optimized.png"),
+  ] {
+    // Clones share the key, the settings and the connections.
+    let client = client.clone();
+    let store =
+      Store::s3(&access_key_id, &secret_access_key, "us-west-1", path);
+
+    // Tinify saves the result in the bucket, only its url comes back.
+    batch
+      .spawn(async move { client.from_file(input).await?.store(store).await });
+  }
+
+  // A failing image doesn't stop the others.
+  while let Some(stored) = batch.join_next().await {
+    match stored? {
+      Ok(url) => println!("Stored at {}", url),
+      Err(TinifyError::ClientError { ref upstream }) => {
+        println!("Error: {} message: {}", upstream.error, upstream.message);
+      }
+      Err(error) => println!("{:?}", error),
+    }
+  }
+
+  Ok(())
+}
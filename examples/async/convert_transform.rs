@@ -1,8 +1,4 @@
-use tinify::error::TinifyError;
-use tinify::transform::Transform;
-use tinify::async_bin::Tinify;
-use tinify::convert::Convert;
-use tinify::convert::Type;
+use tinify::prelude::*;
 use std::path::Path;
 
 #[tokio::main]
@@ -12,7 +8,7 @@ async fn main() -> Result<(), TinifyError> {
     r#type: vec![Type::Jpeg],
   };
   let transform = Transform {
-    background: "#800020".to_string(),
+    background: Background::rgb(128, 0, 32),
   };
   let output = Path::new("./optimized.jpg");
   let tinify = Tinify::new().set_key(key);
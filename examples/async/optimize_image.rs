@@ -1,5 +1,4 @@
-use tinify::error::TinifyError;
-use tinify::async_bin::Tinify;
+use tinify::prelude::*;
 use std::path::Path;
 
 #[tokio::main]
@@ -1,7 +1,4 @@
-use tinify::error::TinifyError;
-use tinify::async_bin::Tinify;
-use tinify::resize::Resize;
-use tinify::resize::Method;
+use tinify::prelude::*;
 use std::path::Path;
 
 #[tokio::main]
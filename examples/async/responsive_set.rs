@@ -0,0 +1,26 @@
+use tinify::prelude::*;
+use std::fs;
+
+#[tokio::main]
+async fn main() -> Result<(), TinifyError> {
+  let key = "api key";
+  let tinify = Tinify::new().set_key(key);
+  let variants = tinify
+    .get_async_client()?
+    .from_file("./unoptimized.jpg").await?
+    .responsive_set(&[480, 800, 1200], Type::Webp).await;
+
+  match variants {
+    Ok(variants) => {
+      for (width, buffer) in variants {
+        fs::write(format!("./optimized-{}w.webp", width), buffer)?;
+      }
+    }
+    Err(TinifyError::ClientError { ref upstream }) => {
+      println!("Error: {} message: {}", upstream.error, upstream.message);
+    }
+    Err(error) => println!("{:?}", error),
+  }
+
+  Ok(())
+}
@@ -0,0 +1,26 @@
+use tinify::prelude::*;
+
+#[tokio::main]
+async fn main() -> Result<(), TinifyError> {
+  let key = "api key";
+  let tinify = Tinify::new().set_key(key);
+  let client = tinify.get_async_client()?;
+
+  for (input, output) in [
+    ("./unoptimized.jpg", "./optimized.jpg"),
+    ("./unoptimized.png", "./optimized.png"),
+  ] {
+    let optimized = client.from_file(input).await?.to_file(output).await;
+
+    if let Err(error) = optimized {
+      match error {
+        TinifyError::ClientError { ref upstream } => {
+          println!("Error: {} message: {}", upstream.error, upstream.message);
+        }
+        _ => println!("{:?}", error),
+      }
+    }
+  }
+
+  Ok(())
+}
@@ -1,8 +1,4 @@
-use tinify::error::TinifyError;
-use tinify::transform::Transform;
-use tinify::convert::Convert;
-use tinify::convert::Type;
-use tinify::sync::Tinify;
+use tinify::prelude::*;
 use std::path::Path;
 
 fn main() -> Result<(), TinifyError> {
@@ -11,7 +7,7 @@ fn main() -> Result<(), TinifyError> {
     r#type: vec![Type::Jpeg],
   };
   let transform = Transform {
-    background: "#800020".to_string(),
+    background: Background::rgb(128, 0, 32),
   };
   let output = Path::new("./optimized.jpg");
   let tinify = Tinify::new().set_key(key);
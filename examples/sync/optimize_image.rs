@@ -1,5 +1,4 @@
-use tinify::error::TinifyError;
-use tinify::sync::Tinify;
+use tinify::prelude::*;
 use std::path::Path;
 
 fn main() -> Result<(), TinifyError> {
@@ -1,7 +1,4 @@
-use tinify::error::TinifyError;
-use tinify::resize::Method;
-use tinify::resize::Resize;
-use tinify::sync::Tinify;
+use tinify::prelude::*;
 use std::path::Path;
 
 fn main() -> Result<(), TinifyError> {
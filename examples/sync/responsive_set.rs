@@ -0,0 +1,25 @@
+use tinify::prelude::*;
+use std::fs;
+
+fn main() -> Result<(), TinifyError> {
+  let key = "api key";
+  let tinify = Tinify::new().set_key(key);
+  let variants = tinify
+    .get_client()?
+    .from_file("./unoptimized.jpg")?
+    .responsive_set(&[480, 800, 1200], Type::Webp);
+
+  match variants {
+    Ok(variants) => {
+      for (width, buffer) in variants {
+        fs::write(format!("./optimized-{}w.webp", width), buffer)?;
+      }
+    }
+    Err(TinifyError::ClientError { ref upstream }) => {
+      println!("Error: {} message: {}", upstream.error, upstream.message);
+    }
+    Err(error) => println!("{:?}", error),
+  }
+
+  Ok(())
+}
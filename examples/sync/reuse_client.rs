@@ -0,0 +1,25 @@
+use tinify::prelude::*;
+
+fn main() -> Result<(), TinifyError> {
+  let key = "api key";
+  let tinify = Tinify::new().set_key(key);
+  let client = tinify.get_client()?;
+
+  for (input, output) in [
+    ("./unoptimized.jpg", "./optimized.jpg"),
+    ("./unoptimized.png", "./optimized.png"),
+  ] {
+    let optimized = client.from_file(input)?.to_file(output);
+
+    if let Err(error) = optimized {
+      match error {
+        TinifyError::ClientError { ref upstream } => {
+          println!("Error: {} message: {}", upstream.error, upstream.message);
+        }
+        _ => println!("{:?}", error),
+      }
+    }
+  }
+
+  Ok(())
+}
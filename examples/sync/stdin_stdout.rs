@@ -0,0 +1,10 @@
+use tinify::prelude::*;
+
+// cat unoptimized.png | cargo run --example stdin_stdout > optimized.png
+fn main() -> Result<(), TinifyError> {
+  let key = "api key";
+  let tinify = Tinify::new().set_key(key);
+  let client = tinify.get_client()?;
+
+  client.compress_stdin_stdout()
+}
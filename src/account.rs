@@ -0,0 +1,8 @@
+/// What the API reports about the account of a key.
+///
+/// The Tinify API only sends the number of compressions made this month, in the `Compression-Count` header of every response. It exposes no plan, account type or API version, so free and paid accounts cannot be told apart from here.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountInfo {
+  /// The number of compressions made this month, `None` until a response was received.
+  pub compression_count: Option<u32>,
+}
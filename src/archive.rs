@@ -0,0 +1,337 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use crate::format::detect_format;
+use crate::format::DetectedFormat;
+use crate::write_output;
+use crate::Config;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipArchive;
+use zip::ZipWriter;
+
+/// The kind of an archive, told from the extension of its path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+  Zip,
+  Tar,
+}
+
+impl Kind {
+  fn from_path(path: &Path) -> Result<Self, TinifyError> {
+    let extension = path
+      .extension()
+      .and_then(OsStr::to_str)
+      .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+      Some("zip") => Ok(Kind::Zip),
+      Some("tar") => Ok(Kind::Tar),
+      _ => {
+        let upstream = Upstream {
+          error: "Archive".to_string(),
+          message: format!(
+            "Unsupported archive {}, expected a .zip or .tar file.",
+            path.display()
+          ),
+        };
+        Err(TinifyError::ClientError { upstream })
+      }
+    }
+  }
+}
+
+/// How an entry is written back, as it was read.
+#[derive(Debug)]
+enum Meta {
+  Zip {
+    dir: bool,
+    options: SimpleFileOptions,
+  },
+  Tar(Box<tar::Header>),
+}
+
+/// An entry of an archive, with its name and contents.
+#[derive(Debug)]
+pub(crate) struct Entry {
+  pub(crate) name: String,
+  pub(crate) data: Vec<u8>,
+  meta: Meta,
+}
+
+impl Entry {
+  /// Whether the entry is a file holding an image Tinify compresses, told from its content like an upload.
+  ///
+  /// GIF and SVG files are no such images, they pass through unchanged like any other file.
+  pub(crate) fn is_image(&self) -> bool {
+    let file = match &self.meta {
+      Meta::Zip { dir, .. } => !dir,
+      Meta::Tar(header) => header.entry_type().is_file(),
+    };
+
+    file
+      && matches!(
+        detect_format(&self.data),
+        Some(
+          DetectedFormat::Png
+            | DetectedFormat::Jpeg
+            | DetectedFormat::Webp
+            | DetectedFormat::Avif
+        )
+      )
+  }
+}
+
+/// A zip or tar archive held in memory, its entries in order.
+#[derive(Debug)]
+pub(crate) struct Archive {
+  kind: Kind,
+  pub(crate) entries: Vec<Entry>,
+}
+
+impl Archive {
+  /// Read every entry of the archive at `path`.
+  pub(crate) fn read(path: &Path) -> Result<Self, TinifyError> {
+    let kind = Kind::from_path(path)?;
+    let file = File::open(path)?;
+    let entries = match kind {
+      Kind::Zip => read_zip(file)?,
+      Kind::Tar => read_tar(file)?,
+    };
+
+    Ok(Self { kind, entries })
+  }
+
+  /// Write the entries to a new archive at `path`, atomically with the `config` of every output, which has to be of the same kind.
+  pub(crate) fn write(
+    &self,
+    config: &Config,
+    path: &Path,
+  ) -> Result<(), TinifyError> {
+    if Kind::from_path(path)? != self.kind {
+      let upstream = Upstream {
+        error: "Archive".to_string(),
+        message: format!(
+          "The archive {} has to be of the same kind as the input.",
+          path.display()
+        ),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+
+    let bytes = match self.kind {
+      Kind::Zip => self.zip_bytes()?,
+      Kind::Tar => self.tar_bytes()?,
+    };
+
+    write_output(config, path, &bytes)
+  }
+
+  fn zip_bytes(&self) -> Result<Vec<u8>, TinifyError> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for entry in &self.entries {
+      if let Meta::Zip { dir, options } = &entry.meta {
+        if *dir {
+          writer
+            .add_directory(entry.name.as_str(), *options)
+            .map_err(io::Error::from)?;
+        } else {
+          writer
+            .start_file(entry.name.as_str(), *options)
+            .map_err(io::Error::from)?;
+          writer.write_all(&entry.data)?;
+        }
+      }
+    }
+
+    Ok(writer.finish().map_err(io::Error::from)?.into_inner())
+  }
+
+  fn tar_bytes(&self) -> Result<Vec<u8>, TinifyError> {
+    let mut builder = tar::Builder::new(Vec::new());
+
+    for entry in &self.entries {
+      if let Meta::Tar(header) = &entry.meta {
+        let mut header = header.as_ref().clone();
+        header.set_size(entry.data.len() as u64);
+        builder.append_data(&mut header, &entry.name, entry.data.as_slice())?;
+      }
+    }
+
+    Ok(builder.into_inner()?)
+  }
+}
+
+fn read_zip(file: File) -> Result<Vec<Entry>, TinifyError> {
+  let mut archive = ZipArchive::new(file).map_err(io::Error::from)?;
+  let mut entries = Vec::with_capacity(archive.len());
+
+  for i in 0..archive.len() {
+    let mut file = archive.by_index(i).map_err(io::Error::from)?;
+    let mut options =
+      SimpleFileOptions::default().compression_method(file.compression());
+    if let Some(modified) = file.last_modified() {
+      options = options.last_modified_time(modified);
+    }
+    if let Some(mode) = file.unix_mode() {
+      options = options.unix_permissions(mode);
+    }
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    entries.push(Entry {
+      name: file.name().to_string(),
+      data,
+      meta: Meta::Zip {
+        dir: file.is_dir(),
+        options,
+      },
+    });
+  }
+
+  Ok(entries)
+}
+
+fn read_tar(file: File) -> Result<Vec<Entry>, TinifyError> {
+  let mut archive = tar::Archive::new(file);
+  let mut entries = Vec::new();
+
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+    let header = Box::new(entry.header().clone());
+
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    entries.push(Entry {
+      name,
+      data,
+      meta: Meta::Tar(header),
+    });
+  }
+
+  Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::tiny_png;
+  use std::env;
+  use std::fs;
+
+  const GIF: &[u8] = b"GIF89a\x01\0\x01\0\0\0\0;";
+  const SVG: &[u8] = b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>";
+
+  fn check_round_trip(input: &Path, output: &Path) -> Result<(), TinifyError> {
+    let archive = Archive::read(input)?;
+    let images: Vec<bool> =
+      archive.entries.iter().map(Entry::is_image).collect();
+    assert_eq!(images, [false, true, false, false, false]);
+
+    archive.write(&Config::default(), output)?;
+    let written = Archive::read(output)?;
+    let names: Vec<&str> = written
+      .entries
+      .iter()
+      .map(|entry| entry.name.as_str())
+      .collect();
+    assert_eq!(
+      names,
+      [
+        "assets/",
+        "assets/logo.png",
+        "assets/README.txt",
+        "assets/spinner.gif",
+        "assets/icon.svg"
+      ]
+    );
+    assert_eq!(written.entries[1].data, tiny_png());
+    assert_eq!(written.entries[2].data, b"not an image");
+    assert_eq!(written.entries[3].data, GIF);
+    assert_eq!(written.entries[4].data, SVG);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_zip_round_trip() -> Result<(), TinifyError> {
+    let dir = env::temp_dir().join("tinify_archive_zip");
+    fs::create_dir_all(&dir)?;
+    let input = dir.join("input.zip");
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    writer.add_directory("assets/", options).unwrap();
+    writer.start_file("assets/logo.png", options).unwrap();
+    writer.write_all(tiny_png())?;
+    writer.start_file("assets/README.txt", options).unwrap();
+    writer.write_all(b"not an image")?;
+    writer.start_file("assets/spinner.gif", options).unwrap();
+    writer.write_all(GIF)?;
+    writer.start_file("assets/icon.svg", options).unwrap();
+    writer.write_all(SVG)?;
+    fs::write(&input, writer.finish().unwrap().into_inner())?;
+
+    check_round_trip(&input, &dir.join("output.zip"))?;
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_tar_round_trip() -> Result<(), TinifyError> {
+    let dir = env::temp_dir().join("tinify_archive_tar");
+    fs::create_dir_all(&dir)?;
+    let input = dir.join("input.tar");
+
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_size(0);
+    builder.append_data(&mut header, "assets/", io::empty())?;
+    for (name, data) in [
+      ("assets/logo.png", &tiny_png()[..]),
+      ("assets/README.txt", b"not an image".as_slice()),
+      ("assets/spinner.gif", GIF),
+      ("assets/icon.svg", SVG),
+    ] {
+      let mut header = tar::Header::new_gnu();
+      header.set_mode(0o644);
+      header.set_size(data.len() as u64);
+      builder.append_data(&mut header, name, data)?;
+    }
+    fs::write(&input, builder.into_inner()?)?;
+
+    check_round_trip(&input, &dir.join("output.tar"))?;
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_mismatched_kind() -> Result<(), TinifyError> {
+    let archive = Archive {
+      kind: Kind::Zip,
+      entries: Vec::new(),
+    };
+
+    assert!(matches!(
+      archive.write(&Config::default(), Path::new("./output.tar")),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Archive"
+    ));
+    assert!(matches!(
+      Archive::read(Path::new("./assets.7z")),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Archive"
+    ));
+
+    Ok(())
+  }
+}
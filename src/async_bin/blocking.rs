@@ -0,0 +1,70 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use std::future::Future;
+use tokio::runtime::Builder;
+use tokio::runtime::Handle;
+
+/// Run an async client call to completion from sync code, on a small runtime of its own.
+///
+/// This gives sync callers the async client without the blocking reqwest stack. It fails inside an existing runtime, where the future should be awaited instead.
+///
+/// # Examples
+///
+/// ```
+/// use tinify::async_bin::block_on;
+/// use tinify::async_bin::Tinify;
+/// use tinify::error::TinifyError;
+///
+/// fn main() -> Result<(), TinifyError> {
+///   let client = Tinify::new().set_key("api key").get_async_client()?;
+///
+///   block_on(async {
+///     client
+///       .from_file("./unoptimized.jpg")
+///       .await?
+///       .to_file("./optimized.jpg")
+///       .await
+///   })
+/// }
+/// ```
+pub fn block_on<F, T>(future: F) -> Result<T, TinifyError>
+where
+  F: Future<Output = Result<T, TinifyError>>,
+{
+  if Handle::try_current().is_ok() {
+    let upstream = Upstream {
+      error: "Runtime".to_string(),
+      message:
+        "Cannot block inside an async runtime, await the future instead."
+          .to_string(),
+    };
+    return Err(TinifyError::ClientError { upstream });
+  }
+
+  let runtime = Builder::new_current_thread().enable_all().build()?;
+
+  runtime.block_on(future)
+}
+
+#[cfg(test)]
+#[cfg(feature = "async")]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+
+  #[test]
+  fn test_block_on() -> Result<(), TinifyError> {
+    let value = block_on(async { Ok(42) })?;
+
+    assert_eq!(value, 42);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_block_on_inside_runtime() {
+    let request = block_on(async { Ok(()) });
+
+    assert_matches!(request, Err(TinifyError::ClientError { .. }));
+  }
+}
@@ -1,10 +1,57 @@
+use crate::account::AccountInfo;
+#[cfg(feature = "archive")]
+use crate::archive::Archive;
 use crate::async_bin::source::Source;
+use crate::cache;
 use crate::error::TinifyError;
+use crate::format::check_content;
+use crate::format::check_extension;
+use crate::hash::HashAlgorithm;
+use crate::hash::HashedImage;
+use crate::in_place::back_up;
+#[cfg(feature = "archive")]
+use crate::in_place::ArchiveReport;
+use crate::in_place::InPlace;
+use crate::in_place::InPlaceReport;
+use crate::state::Empty;
+use crate::template::OutputTemplate;
+use crate::write_atomic;
+use crate::write_output;
+use crate::CompressionCount;
+use crate::Config;
+use reqwest::Client as ReqwestClient;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::task;
 
 /// The Tinify Client.
+///
+/// The client only holds the key, its settings and a connection pool, every `from_*` call starts a new [`Source`]. Cloning it is cheap, the clones and their sources share the same key, settings and connections instead of copying them.
+#[derive(Clone)]
+#[must_use = "a client does nothing until an image is chosen with `from_file`, `from_buffer` or `from_url`"]
 pub struct Client {
-  source: Source,
+  key: Arc<str>,
+  reqwest_client: ReqwestClient,
+  config: Arc<Config>,
+  compression_count: CompressionCount,
+}
+
+/// The key is redacted, so a client can be logged or shown in a panic message.
+impl fmt::Debug for Client {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Client")
+      .field("key", &"<redacted>")
+      .field("reqwest_client", &self.reqwest_client)
+      .field("config", &self.config)
+      .field("compression_count", &self.compression_count)
+      .finish()
+  }
 }
 
 impl Client {
@@ -13,29 +60,368 @@ impl Client {
     K: AsRef<str>,
   {
     Self {
-      source: Source::new(Some(key.as_ref())),
+      key: Arc::from(key.as_ref()),
+      reqwest_client: ReqwestClient::new(),
+      config: Arc::default(),
+      compression_count: CompressionCount::default(),
+    }
+  }
+
+  pub(crate) fn with_config(mut self, config: Config) -> Self {
+    self.config = Arc::new(config);
+    self
+  }
+
+  /// Build the connection pool with the settings of the config, the pool of `new` has the defaults of reqwest.
+  pub(crate) fn with_pool(mut self) -> Result<Self, TinifyError> {
+    if let Some(idle) = self.config.pool_max_idle_per_host {
+      self.reqwest_client = ReqwestClient::builder()
+        .pool_max_idle_per_host(idle)
+        .build()?;
+    }
+    Ok(self)
+  }
+
+  fn source(&self) -> Source<Empty> {
+    Source::new(
+      Some(Arc::clone(&self.key)),
+      self.reqwest_client.clone(),
+      self.config.clone(),
+      self.compression_count.clone(),
+    )
+  }
+
+  /// Get the number of compressions made this month, as reported by the latest response to this client or its sources.
+  ///
+  /// This is `None` until a response carried a `Compression-Count` header, and only reflects the most recent one.
+  pub fn last_compression_count(&self) -> Option<u32> {
+    self.compression_count.get()
+  }
+
+  /// Get what the API reported about the account, from the latest response to this client or its sources.
+  ///
+  /// Every field is `None` until a response was received.
+  pub fn account_info(&self) -> AccountInfo {
+    AccountInfo {
+      compression_count: self.compression_count.get(),
     }
   }
 
   /// Choose a file to compress.
-  pub async fn from_file<P>(self, path: P) -> Result<Source, TinifyError>
+  pub async fn from_file<P>(&self, path: P) -> Result<Source, TinifyError>
+  where
+    P: AsRef<Path>,
+  {
+    self.source().from_file(path, |_, _| {}).await
+  }
+
+  /// Choose a file to compress like [`from_file`](Self::from_file), calling `progress` with the bytes read so far and the size of the file while reading it.
+  ///
+  /// The upload is a single request once the file is in memory, so reading it is the part of compressing a large file a progress bar can follow. `progress` is first called with none read, then after every megabyte, the last call has both values equal.
+  pub async fn from_file_with_progress<P, F>(
+    &self,
+    path: P,
+    progress: F,
+  ) -> Result<Source, TinifyError>
   where
     P: AsRef<Path>,
+    F: FnMut(u64, u64),
   {
-    self.source.from_file(path).await
+    self.source().from_file(path, progress).await
   }
 
   /// Choose a buffer to compress.
-  pub async fn from_buffer(self, buffer: &[u8]) -> Result<Source, TinifyError> {
-    self.source.from_buffer(buffer).await
+  pub async fn from_buffer(
+    &self,
+    buffer: &[u8],
+  ) -> Result<Source, TinifyError> {
+    self.source().from_buffer(buffer).await
+  }
+
+  /// Choose a [`Bytes`](bytes::Bytes) buffer to compress, uploaded without copying it.
+  #[cfg(feature = "bytes")]
+  pub async fn from_bytes(
+    &self,
+    bytes: bytes::Bytes,
+  ) -> Result<Source, TinifyError> {
+    self.source().from_bytes(bytes).await
+  }
+
+  /// Choose an SVG image to compress, rasterized to a `width` by `height` PNG first.
+  #[cfg(feature = "svg")]
+  pub async fn from_svg(
+    &self,
+    svg: &[u8],
+    width: u32,
+    height: u32,
+  ) -> Result<Source, TinifyError> {
+    self.source().from_svg(svg, width, height).await
+  }
+
+  /// Choose the first frame of a GIF to compress, decoded locally and uploaded as a PNG, since Tinify doesn't accept GIF.
+  ///
+  /// This gives an optimized poster frame of an animation, chain a `convert` for another type. The animation is lost, only the first frame is kept. An input that isn't a GIF fails with a `ClientError` whose error is `Gif`.
+  #[cfg(feature = "gif")]
+  pub async fn from_gif(&self, gif: &[u8]) -> Result<Source, TinifyError> {
+    self.source().from_gif(gif).await
   }
 
   /// Choose an url image to compress.
-  pub async fn from_url<P>(self, url: P) -> Result<Source, TinifyError>
+  ///
+  /// Tinify downloads the image itself, so only `http` and `https` urls are accepted. Anything else fails before the request, a malformed url with a `UrlParseError` and another scheme with a `ClientError` whose error is `Url`.
+  pub async fn from_url<P>(&self, url: P) -> Result<Source, TinifyError>
   where
     P: AsRef<str> + Into<String>,
   {
-    self.source.from_url(url).await
+    self.source().from_url(url).await
+  }
+
+  /// Choose an url image on an origin protected by basic auth, like an internal server, to compress.
+  ///
+  /// The image is downloaded here and uploaded like `from_buffer`, the credentials are only sent to the origin.
+  /// Bad credentials fail with a `ClientError` whose error is `Credentials`, an error status of the origin with `Source`
+  /// and a body that isn't a supported image with `Format`.
+  pub async fn from_url_with_auth(
+    &self,
+    url: &str,
+    user: &str,
+    password: &str,
+  ) -> Result<Source, TinifyError> {
+    self.source().from_url_with_auth(url, user, password).await
+  }
+
+  /// Compress all of stdin and write the result to stdout, for piping like `cat in.png | mytool > out.png`.
+  ///
+  /// Stdin and stdout are read and written as raw bytes, there is no newline translation on Windows either.
+  pub async fn compress_stdin_stdout(&self) -> Result<(), TinifyError> {
+    let mut input = Vec::new();
+    tokio::io::stdin().read_to_end(&mut input).await?;
+    let output = self.from_buffer(&input).await?.to_buffer().await?;
+    let mut stdout = tokio::io::stdout();
+    stdout.write_all(&output).await?;
+    stdout.flush().await?;
+
+    Ok(())
+  }
+
+  /// Compress a file and replace it with the result, for optimizing the assets of a repository.
+  ///
+  /// The `mode` has to say whether the original is kept as a backup, a backup left by an earlier run is kept as it is. The file is replaced atomically, a failure leaves the original untouched. With `Tinify::keep_original_if_larger` a file that compressing didn't make smaller is left as it is, without a backup.
+  pub async fn compress_file_in_place<P>(
+    &self,
+    path: P,
+    mode: InPlace,
+  ) -> Result<InPlaceReport, TinifyError>
+  where
+    P: AsRef<Path>,
+  {
+    let path = path.as_ref().to_path_buf();
+    let mut source = self.from_file(&path).await?;
+    let original_size = source.input_size().unwrap_or_default();
+    let compressed = source.saved_output().await?;
+    let compressed_size = compressed.len();
+
+    if source.kept_original() {
+      return Ok(InPlaceReport {
+        original_size,
+        compressed_size,
+      });
+    }
+    if mode == InPlace::Backup {
+      let original = path.clone();
+      task::spawn_blocking(move || back_up(&original)).await??;
+    }
+    task::spawn_blocking(move || write_atomic(&path, &compressed)).await??;
+
+    Ok(InPlaceReport {
+      original_size,
+      compressed_size,
+    })
+  }
+
+  /// Compress a file in place like [`compress_file_in_place`](Self::compress_file_in_place), but only when it is larger than `min_bytes`.
+  ///
+  /// Returns whether the file was compressed. Tiny images like icons gain little from compressing and still cost a compression each, skipping them saves quota, while a `min_bytes` of `0` compresses every file.
+  pub async fn compress_if_larger_than<P>(
+    &self,
+    path: P,
+    min_bytes: u64,
+    mode: InPlace,
+  ) -> Result<bool, TinifyError>
+  where
+    P: AsRef<Path>,
+  {
+    let path = path.as_ref();
+
+    if min_bytes > 0 && tokio::fs::metadata(path).await?.len() <= min_bytes {
+      return Ok(false);
+    }
+    self.compress_file_in_place(path, mode).await?;
+
+    Ok(true)
+  }
+
+  /// Compress the image in `buffer` and replace its contents with the result, for services holding images in memory.
+  ///
+  /// On failure `buffer` is left untouched. With `Tinify::keep_original_if_larger` it keeps the original when compressing didn't make it smaller.
+  pub async fn compress_in_memory(
+    &self,
+    buffer: &mut Vec<u8>,
+  ) -> Result<InPlaceReport, TinifyError> {
+    let original_size = buffer.len();
+    let compressed = self.from_buffer(buffer).await?.saved_output().await?;
+    let compressed_size = compressed.len();
+    *buffer = compressed;
+
+    Ok(InPlaceReport {
+      original_size,
+      compressed_size,
+    })
+  }
+
+  /// Compress the image in `buffer` and hash the result with `algorithm`, for fingerprinted file names like `logo.ab12cd34.webp`.
+  ///
+  /// The hash covers the compressed bytes, so it only changes when the output does.
+  pub async fn compress_and_hash(
+    &self,
+    buffer: &[u8],
+    algorithm: HashAlgorithm,
+  ) -> Result<HashedImage, TinifyError> {
+    let compressed = self.from_buffer(buffer).await?.to_buffer().await?;
+
+    Ok(HashedImage::new(compressed, algorithm))
+  }
+
+  /// Compress `input` to `output` unless `output` is already newer, like `make` does.
+  ///
+  /// Returns whether the image was compressed. An `output` modified at the same time as `input` or later is up to date, and the API is not called for it.
+  pub async fn compress_if_newer<P, Q>(
+    &self,
+    input: P,
+    output: Q,
+  ) -> Result<bool, TinifyError>
+  where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+  {
+    let (input, output) = (input.as_ref(), output.as_ref());
+    let modified = tokio::fs::metadata(input).await?.modified()?;
+
+    match tokio::fs::metadata(output).await {
+      Ok(metadata) if metadata.modified()? >= modified => return Ok(false),
+      Ok(_) => {}
+      Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+      Err(err) => return Err(err.into()),
+    }
+    self
+      .from_file(input)
+      .await?
+      .to_file(output.to_path_buf())
+      .await?;
+
+    Ok(true)
+  }
+
+  /// Compress every image in the zip or tar archive `in_archive` and write a new archive of the same kind to `out_archive`, for bundled assets.
+  ///
+  /// Entries keep their order, names and metadata, and entries that are not PNG, JPEG, WebP or AVIF images, like a GIF or an SVG, pass through unchanged. Every image is returned with its sizes or its own error, a failing image is written unchanged and doesn't stop the archive. The kind is told from the extensions, `.zip` or `.tar`, which have to match, and the whole archive is held in memory.
+  #[cfg(feature = "archive")]
+  pub async fn compress_archive<P, Q>(
+    &self,
+    in_archive: P,
+    out_archive: Q,
+  ) -> Result<ArchiveReport, TinifyError>
+  where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+  {
+    let in_archive = in_archive.as_ref().to_path_buf();
+    let mut archive =
+      task::spawn_blocking(move || Archive::read(&in_archive)).await??;
+    let mut results = Vec::new();
+    for entry in archive.entries.iter_mut().filter(|entry| entry.is_image()) {
+      let result = self.compress_in_memory(&mut entry.data).await;
+      results.push((entry.name.clone(), result));
+    }
+
+    let (out_archive, config) =
+      (out_archive.as_ref().to_path_buf(), self.config.clone());
+    task::spawn_blocking(move || archive.write(&config, &out_archive))
+      .await??;
+
+    Ok(results)
+  }
+
+  /// Compress every file in `paths`, saving each one to the path expanded from `template`.
+  ///
+  /// A failing file doesn't stop the batch, every input is returned with the path it was saved to or its own error. With `Tinify::dedup_batches` a file with the same content as an earlier one is saved from the earlier result, without compressing it again.
+  pub async fn compress_many<I, P>(
+    &self,
+    paths: I,
+    template: &OutputTemplate,
+  ) -> Vec<(PathBuf, Result<PathBuf, TinifyError>)>
+  where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+  {
+    let mut results = Vec::new();
+    let mut seen = HashMap::new();
+    for (i, path) in paths.into_iter().enumerate() {
+      let input = path.as_ref().to_path_buf();
+      let output = template.expand(&input, i + 1);
+      let result = self
+        .compress_batch_file(&input, &output, &mut seen)
+        .await
+        .map(|()| output);
+      results.push((input, result));
+    }
+
+    results
+  }
+
+  /// Compress a file of a batch, or copy the result of an earlier file with the same content when batches are deduplicated.
+  ///
+  /// `seen` maps the SHA-256 digest and the length of every compressed input to its output. The file is read once, and uploaded from that buffer.
+  async fn compress_batch_file(
+    &self,
+    input: &Path,
+    output: &Path,
+    seen: &mut HashMap<(cache::Digest, usize), PathBuf>,
+  ) -> Result<(), TinifyError> {
+    if !self.config.dedup_batches {
+      return self
+        .from_file(input)
+        .await?
+        .to_file(output.to_path_buf())
+        .await;
+    }
+
+    if self.config.reject_unsupported_extensions {
+      check_extension(input)?;
+    }
+
+    let bytes = tokio::fs::read(input).await?;
+    let key = (cache::digest(&bytes), bytes.len());
+
+    if let Some(earlier) = seen.get(&key) {
+      let compressed = tokio::fs::read(earlier).await?;
+      let (output, config) = (output.to_path_buf(), self.config.clone());
+      return task::spawn_blocking(move || {
+        write_output(&config, &output, &compressed)
+      })
+      .await?;
+    }
+    if self.config.reject_unsupported_extensions {
+      check_content(input, &bytes)?;
+    }
+    self
+      .from_buffer(&bytes)
+      .await?
+      .to_file(output.to_path_buf())
+      .await?;
+    seen.insert(key, output.to_path_buf());
+
+    Ok(())
   }
 }
 
@@ -45,15 +431,22 @@ mod tests {
   use super::*;
   use crate::convert::Convert;
   use crate::convert::Type;
+  use crate::fixture::seed_cache;
+  use crate::fixture::tiny_png;
+  use crate::mock;
+  use crate::mock::KeepAlive;
+  use crate::mock::Reply;
   use crate::resize::Method;
   use crate::resize::Resize;
   use assert_matches::assert_matches;
   use dotenv::dotenv;
+  use imagesize::blob_size as size_from_buffer;
   use imagesize::size;
   use reqwest::Client as ReqwestClient;
   use std::env;
   use std::ffi::OsStr;
   use std::fs;
+  use std::time::Duration;
 
   fn get_key() -> String {
     dotenv().ok();
@@ -63,6 +456,58 @@ mod tests {
     }
   }
 
+  #[tokio::test]
+  async fn test_with_pool() -> Result<(), TinifyError> {
+    // Unlimited by default, both connections of the first round are reused.
+    for (idle, expected) in [(None, 2), (Some(1), 3), (Some(0), 4)] {
+      let server = KeepAlive::serve(Duration::from_millis(100));
+      let client = Client::new("key")
+        .with_config(Config {
+          pool_max_idle_per_host: idle,
+          ..Config::default()
+        })
+        .with_pool()?;
+      for _ in 0..2 {
+        // Two requests in flight together need two connections.
+        let (first, second) = tokio::join!(
+          client.reqwest_client.get(&server.url).send(),
+          client.reqwest_client.get(&server.url).send(),
+        );
+        first?;
+        second?;
+      }
+
+      assert_eq!(server.connections(), expected, "idle {:?}", idle);
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_debug_redacts_key() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let client = Client::new("s3cret-api-key").with_config(Config {
+      dry_run: true,
+      ..Config::default()
+    });
+    let source = client.from_buffer(png).await?;
+
+    assert!(!format!("{:?}", client).contains("s3cret-api-key"));
+    assert!(!format!("{:?}", source).contains("s3cret-api-key"));
+    assert!(format!("{:?}", source).contains("<redacted>"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_clone_shares_key_and_config() {
+    let client = Client::new("key");
+    let clone = client.clone();
+
+    assert!(Arc::ptr_eq(&client.key, &clone.key));
+    assert!(Arc::ptr_eq(&client.config, &clone.config));
+  }
+
   #[tokio::test]
   async fn test_invalid_key() {
     let client = Client::new("invalid");
@@ -74,6 +519,47 @@ mod tests {
     assert_matches!(request, TinifyError::ClientError { .. });
   }
 
+  #[test]
+  fn test_account_info_without_response() {
+    let client = Client::new("invalid");
+
+    assert_eq!(client.account_info(), AccountInfo::default());
+  }
+
+  #[tokio::test]
+  async fn test_can_convert_to() -> Result<(), TinifyError> {
+    let client = Client::new("invalid").with_config(Config {
+      dry_run: true,
+      ..Config::default()
+    });
+    let jpeg = client.from_file("./tmp_image.jpg").await?;
+    let apng = b"\x89PNG\r\n\x1a\n\0\0\0\x08acTL\0\0\0\x02\0\0\0\0\0\0\0\0";
+    let apng = client.from_buffer(apng).await?;
+    let gif = client.from_buffer(b"GIF89a\x01\0\x01\0").await?;
+
+    assert!(jpeg.can_convert_to(Type::Webp));
+    assert!(jpeg.can_convert_to(Type::Jpeg));
+    assert!(apng.can_convert_to(Type::Png));
+    assert!(!apng.can_convert_to(Type::Webp));
+    assert!(!gif.can_convert_to(Type::Png));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_compress_many_missing_file() -> Result<(), TinifyError> {
+    let template = OutputTemplate::new("{dir}/optimized/{stem}.{ext}")?;
+    let results = Client::new("invalid")
+      .compress_many(["./missing_1.png", "./missing_2.png"], &template)
+      .await;
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[1].0, Path::new("./missing_2.png"));
+    assert_matches!(results[1].1, Err(TinifyError::IOError(_)));
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_compress_from_file() -> Result<(), TinifyError> {
     let key = get_key();
@@ -121,6 +607,79 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_durations() -> Result<(), TinifyError> {
+    let key = get_key();
+    let resize = Resize {
+      method: Method::Scale,
+      width: Some(400),
+      height: None,
+    };
+    let mut source = Client::new(key)
+      .from_file("./tmp_image.jpg")
+      .await?
+      .resize(resize)?;
+
+    assert!(source.upload_duration().is_some());
+    assert!(source.processing_duration().is_none());
+
+    let _ = source.to_buffer().await?;
+
+    assert!(source.processing_duration().is_some());
+    assert!(source.download_duration().is_some());
+
+    Ok(())
+  }
+
+  #[cfg(feature = "bytes")]
+  #[tokio::test]
+  async fn test_compress_from_bytes() -> Result<(), TinifyError> {
+    let key = get_key();
+    let bytes = bytes::Bytes::from(fs::read("./tmp_image.jpg")?);
+    let buffer = Client::new(key)
+      .from_bytes(bytes)
+      .await?
+      .to_buffer()
+      .await?;
+
+    assert_eq!(buffer.len(), 102051);
+
+    Ok(())
+  }
+
+  #[cfg(feature = "stream")]
+  #[tokio::test]
+  async fn test_into_byte_stream() -> Result<(), TinifyError> {
+    use futures_util::TryStreamExt;
+
+    let key = get_key();
+    let client = Client::new(key);
+    let resize = Resize {
+      method: Method::Fit,
+      width: Some(400),
+      height: Some(200),
+    };
+    let expected = client
+      .from_file("./tmp_image.jpg")
+      .await?
+      .resize(resize.clone())?
+      .to_buffer()
+      .await?;
+    let streamed: Vec<u8> = client
+      .from_file("./tmp_image.jpg")
+      .await?
+      .resize(resize)?
+      .into_byte_stream()
+      .await?
+      .map_ok(|chunk| chunk.to_vec())
+      .try_concat()
+      .await?;
+
+    assert_eq!(streamed.len(), expected.len());
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_compress_from_url() -> Result<(), TinifyError> {
     let key = get_key();
@@ -147,6 +706,118 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_reuse_client() -> Result<(), TinifyError> {
+    let key = get_key();
+    let client = Client::new(key);
+    let tmp_image = Path::new("./tmp_image.jpg");
+    let first = client.from_file(tmp_image).await?.to_buffer().await?;
+    let second = client
+      .from_buffer(&fs::read(tmp_image)?)
+      .await?
+      .to_buffer()
+      .await?;
+
+    assert_eq!(first.len(), second.len());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_operations_compression_count() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let upload = |location: &str, count: &str| {
+      Reply::new("201 Created")
+        .header("Location", location)
+        .header("Compression-Count", count)
+    };
+    let result = |count: &str| {
+      Reply::new("200 OK")
+        .header("Compression-Count", count)
+        .body(png)
+    };
+    let (url, server) = mock::serve(vec![
+      upload("/output/first", "1"),
+      result("1"),
+      result("2"),
+      upload("/output/second", "3"),
+      result("3"),
+      result("4"),
+      upload("/output/applied", "5"),
+      result("5"),
+      result("6"),
+    ]);
+    let client = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?;
+    let resize = Resize {
+      method: Method::Scale,
+      width: Some(400),
+      height: None,
+    };
+    let convert = Convert {
+      r#type: vec![Type::Webp],
+    };
+
+    // The shrink, then the resize and the convert in one request.
+    let mut source = client
+      .from_buffer(png)
+      .await?
+      .resize(resize.clone())?
+      .convert(convert.clone())?;
+    source.to_buffer().await?;
+    assert_eq!(source.checked_compression_count()?, Some(2));
+
+    // The shrink, the resize, the shrink of its result and the convert.
+    let mut source = client
+      .from_buffer(png)
+      .await?
+      .resize(resize)?
+      .apply()
+      .await?
+      .convert(convert)?;
+    source.to_buffer().await?;
+    assert_eq!(source.checked_compression_count()?, Some(6));
+    assert_eq!(client.last_compression_count(), Some(6));
+
+    let requests = server.join().unwrap();
+    let compressions: Vec<&str> = requests
+      .iter()
+      .filter_map(|request| request.strip_prefix("POST ")?.split(' ').next())
+      .collect();
+    assert_eq!(
+      compressions,
+      [
+        "/shrink",
+        "/output/first",
+        "/shrink",
+        "/output/second",
+        "/shrink",
+        "/output/applied"
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_input_size() -> Result<(), TinifyError> {
+    let key = get_key();
+    let client = Client::new(key);
+    let from_file = client.from_file("./tmp_image.jpg").await?;
+    let from_url = client
+      .from_url("https://tinypng.com/images/panda-happy.png")
+      .await?;
+
+    assert_eq!(from_file.input_size(), Some(124814));
+    assert_eq!(from_url.input_size(), None);
+    assert_eq!(from_file.input_orientation(), None);
+    assert_eq!(from_url.input_orientation(), None);
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_save_to_file() -> Result<(), TinifyError> {
     let key = get_key();
@@ -184,6 +855,197 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_write_to_vec() -> Result<(), TinifyError> {
+    let key = get_key();
+    let mut buffer = b"prefix".to_vec();
+    Client::new(key)
+      .from_file("./tmp_image.jpg")
+      .await?
+      .write_to_vec(&mut buffer)
+      .await?;
+
+    assert!(buffer.starts_with(b"prefix"));
+    assert_eq!(buffer.len(), 6 + 102051);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_compress_file_in_place() -> Result<(), TinifyError> {
+    let key = get_key();
+    let path = Path::new("./tmp_in_place_async.jpg");
+    let backup = Path::new("./tmp_in_place_async.jpg.orig");
+    fs::copy("./tmp_image.jpg", path)?;
+    let report = Client::new(key)
+      .compress_file_in_place(path, InPlace::Backup)
+      .await?;
+
+    assert_eq!(report.bytes_saved(), 124814 - 102051);
+    assert_eq!(fs::metadata(path)?.len(), 102051);
+    assert_eq!(fs::metadata(backup)?.len(), 124814);
+
+    fs::remove_file(path)?;
+    fs::remove_file(backup)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_compress_file_in_place_twice() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_in_place_twice");
+    for (input, output) in [(&png[..], &b"first"[..]), (b"first", b"second")] {
+      seed_cache(
+        &dir.join("cache"),
+        input,
+        &crate::Operations::default(),
+        output,
+      )?;
+    }
+    let path = dir.join("logo.png");
+    fs::write(&path, png)?;
+    let client = crate::async_bin::Tinify::new()
+      .set_cache_dir(dir.join("cache"))
+      .get_async_client()?;
+
+    client
+      .compress_file_in_place(&path, InPlace::Backup)
+      .await?;
+    client
+      .compress_file_in_place(&path, InPlace::Backup)
+      .await?;
+
+    assert_eq!(fs::read(&path)?, b"second");
+    assert_eq!(fs::read(dir.join("logo.png.orig"))?, png);
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_empty_file() -> Result<(), TinifyError> {
+    let path = Path::new("./tmp_empty_async.png");
+    fs::write(path, b"")?;
+    let request = Client::new("").from_file(path).await;
+    fs::remove_file(path)?;
+
+    assert_matches!(request, Err(TinifyError::ClientError { ref upstream }) if upstream.error == "Empty");
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_compress_if_larger_than_skips_small_files(
+  ) -> Result<(), TinifyError> {
+    let path = Path::new("./tmp_small_async.png");
+    fs::write(path, b"\x89PNG\r\n\x1a\n")?;
+    let compressed = Client::new("")
+      .compress_if_larger_than(path, 1024, InPlace::Overwrite)
+      .await?;
+
+    assert!(!compressed);
+    assert_eq!(fs::read(path)?, b"\x89PNG\r\n\x1a\n");
+
+    fs::remove_file(path)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_compress_in_memory() -> Result<(), TinifyError> {
+    let key = get_key();
+    let mut buffer = fs::read("./tmp_image.jpg")?;
+    let report = Client::new(key).compress_in_memory(&mut buffer).await?;
+
+    assert_eq!(report.bytes_saved(), 124814 - 102051);
+    assert_eq!(buffer.len(), 102051);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_keep_original_in_place() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_keep_original_in_place");
+    seed_cache(
+      &dir.join("cache"),
+      png,
+      &crate::Operations::default(),
+      &[0; 64],
+    )?;
+    let path = dir.join("logo.png");
+    fs::write(&path, png)?;
+    let client = crate::async_bin::Tinify::new()
+      .set_cache_dir(dir.join("cache"))
+      .keep_original_if_larger(true)
+      .get_async_client()?;
+    let mut buffer = png.to_vec();
+
+    let in_place = client
+      .compress_file_in_place(&path, InPlace::Backup)
+      .await?;
+    let in_memory = client.compress_in_memory(&mut buffer).await?;
+
+    assert_eq!(in_place.compressed_size, png.len());
+    assert_eq!(fs::read(&path)?, png);
+    assert!(!dir.join("logo.png.orig").exists());
+    assert_eq!(in_memory.bytes_saved(), 0);
+    assert_eq!(buffer, png);
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_save_both() -> Result<(), TinifyError> {
+    let key = get_key();
+    let output = Path::new("./tmp_save_both_async.jpg");
+    let sidecar = Client::new(key)
+      .from_file("./tmp_image.jpg")
+      .await?
+      .save_both(output)
+      .await?;
+    let json: serde_json::Value = serde_json::from_slice(&fs::read(&sidecar)?)?;
+
+    assert_eq!(sidecar, Path::new("./tmp_save_both_async.jpg.json"));
+    assert_eq!(json["input_size"], 124814);
+    assert_eq!(json["output_size"], fs::metadata(output)?.len());
+    assert_eq!(json["operations"], "none");
+
+    fs::remove_file(output)?;
+    fs::remove_file(sidecar)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_response_headers() -> Result<(), TinifyError> {
+    let key = get_key();
+    let source = Client::new(key).from_file("./tmp_image.jpg").await?;
+    let headers = source.response_headers().unwrap();
+
+    assert_eq!(headers["content-type"], "image/jpeg");
+    assert!(!headers.contains_key("authorization"));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_to_data_uri() -> Result<(), TinifyError> {
+    let key = get_key();
+    let mut source = Client::new(key).from_file("./tmp_image.jpg").await?;
+    let base64 = source.to_base64().await?;
+    let data_uri = source.to_data_uri().await?;
+
+    assert_eq!(base64.len(), 102051_usize.div_ceil(3) * 4);
+    assert_eq!(data_uri, format!("data:image/jpeg;base64,{}", base64));
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_resize_scale_width() -> Result<(), TinifyError> {
     let key = get_key();
@@ -271,6 +1133,24 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_resize_fit_dimensions() -> Result<(), TinifyError> {
+    let key = get_key();
+    let mut source = Client::new(key)
+      .from_url("https://tinypng.com/images/panda-happy.png")
+      .await?
+      .resize(Resize::fit_within(400, 400))?;
+    let buffer = source.to_buffer().await?;
+    let dim = size_from_buffer(&buffer).unwrap();
+
+    assert_eq!(
+      source.dimensions(),
+      Some((dim.width as u32, dim.height as u32))
+    );
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_resize_cover() -> Result<(), TinifyError> {
     let key = get_key();
@@ -329,6 +1209,25 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_responsive_set() -> Result<(), TinifyError> {
+    let key = get_key();
+    let variants = Client::new(key)
+      .from_file("./tmp_image.jpg")
+      .await?
+      .responsive_set(&[200, 400], Type::Webp)
+      .await?;
+    let widths: Vec<usize> = variants
+      .iter()
+      .map(|(_, buffer)| size_from_buffer(buffer).unwrap().width)
+      .collect();
+
+    assert_eq!(widths, vec![200, 400]);
+    assert_eq!(variants[0].0, 200);
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_error_transparent_png_to_jpeg() -> Result<(), TinifyError> {
     let key = get_key();
@@ -423,6 +1322,68 @@ mod tests {
     Ok(())
   }
 
+  #[tokio::test]
+  async fn test_convert_png_to_png() -> Result<(), TinifyError> {
+    let key = get_key();
+    let convert = Convert {
+      r#type: vec![Type::Png],
+    };
+    let (mut source, r#type) = Client::new(key)
+      .from_url("https://tinypng.com/images/panda-happy.png")
+      .await?
+      .convert_and_report(convert)
+      .await?;
+
+    assert_matches!(r#type, Type::Png);
+    assert!(!source.to_buffer().await?.is_empty());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_convert_jpg_to_jpg() -> Result<(), TinifyError> {
+    let key = get_key();
+    let convert = Convert {
+      r#type: vec![Type::Jpeg],
+    };
+    let (mut source, r#type) = Client::new(key)
+      .from_file("./tmp_image.jpg")
+      .await?
+      .convert_and_report(convert)
+      .await?;
+
+    assert_matches!(r#type, Type::Jpeg);
+    assert!(!source.to_buffer().await?.is_empty());
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_convert_and_report() -> Result<(), TinifyError> {
+    let key = get_key();
+    let convert = Convert {
+      r#type: vec![Type::Png, Type::Webp],
+    };
+    let (mut source, r#type) = Client::new(key)
+      .from_url("https://tinypng.com/images/panda-happy.png")
+      .await?
+      .convert_and_report(convert)
+      .await?;
+
+    assert_matches!(r#type, Type::Webp);
+    assert_eq!(source.content_type(), Some("image/webp"));
+    #[cfg(feature = "mime")]
+    assert_eq!(source.mime_type(), "image/webp".parse().ok());
+    let buffer = source.to_buffer().await?;
+    assert!(!buffer.is_empty());
+    assert_eq!(
+      source.convert_candidates(),
+      vec![(Type::Webp, buffer.len())]
+    );
+
+    Ok(())
+  }
+
   #[tokio::test]
   async fn test_convert_smallest_wildcard_type() -> Result<(), TinifyError> {
     let key = get_key();
@@ -447,4 +1408,39 @@ mod tests {
 
     Ok(())
   }
+
+  #[tokio::test]
+  async fn test_compress_if_newer_up_to_date() -> Result<(), TinifyError> {
+    let input = env::temp_dir().join("tinify_async_newer_input.jpg");
+    let output = env::temp_dir().join("tinify_async_newer_output.jpg");
+    fs::write(&input, b"input")?;
+    fs::write(&output, b"output")?;
+    let client = Client::new("invalid");
+    let compressed = client.compress_if_newer(&input, &output).await?;
+    let missing = client.compress_if_newer("./missing.jpg", &output).await;
+
+    fs::remove_file(&input)?;
+    fs::remove_file(&output)?;
+
+    assert!(!compressed);
+    assert_matches!(missing, Err(TinifyError::IOError(_)));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_compress_if_newer() -> Result<(), TinifyError> {
+    let key = get_key();
+    let output = Path::new("./tmp_newer_async.jpg");
+    let client = Client::new(key);
+    let compressed =
+      client.compress_if_newer("./tmp_image.jpg", output).await?;
+
+    assert!(compressed);
+    assert!(!client.compress_if_newer("./tmp_image.jpg", output).await?);
+
+    fs::remove_file(output)?;
+
+    Ok(())
+  }
 }
@@ -1,7 +1,14 @@
+mod blocking;
 mod client;
+mod shortcuts;
 mod source;
 mod tinify;
 
+pub use self::blocking::block_on;
 pub use self::client::Client;
+pub use self::shortcuts::client_from_env;
+pub use self::shortcuts::compress;
+pub use self::shortcuts::from_file;
+pub use self::shortcuts::KEY_VAR;
 pub use self::source::Source;
 pub use self::tinify::Tinify;
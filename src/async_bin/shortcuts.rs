@@ -0,0 +1,82 @@
+use crate::async_bin::client::Client;
+use crate::async_bin::source::Source;
+use crate::async_bin::tinify::Tinify;
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use std::env;
+use std::path::Path;
+
+/// The environment variable [`client_from_env`] and [`compress`] read the key from.
+pub const KEY_VAR: &str = "TINIFY_KEY";
+
+/// Create an async client with the key in the `TINIFY_KEY` environment variable.
+///
+/// Fails with a `ClientError` whose error is `Key` when the variable is missing or empty. Nothing is stored globally, every call creates a new client.
+pub fn client_from_env() -> Result<Client, TinifyError> {
+  match env::var(KEY_VAR) {
+    Ok(key) if !key.trim().is_empty() => {
+      Tinify::new().set_key(key.trim()).get_async_client()
+    }
+    _ => {
+      let upstream = Upstream {
+        error: "Key".to_string(),
+        message: format!("The `{}` environment variable is not set.", KEY_VAR),
+      };
+      Err(TinifyError::ClientError { upstream })
+    }
+  }
+}
+
+/// Choose a file to compress with the given key, without building a client first.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tinify::async_bin::from_file;
+/// use tinify::error::TinifyError;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), TinifyError> {
+///   from_file("api key", "./unoptimized.jpg")
+///     .await?
+///     .to_file("./optimized.jpg")
+///     .await
+/// }
+/// ```
+pub async fn from_file<K, P>(key: K, path: P) -> Result<Source, TinifyError>
+where
+  K: Into<String>,
+  P: AsRef<Path>,
+{
+  Tinify::new()
+    .set_key(key)
+    .get_async_client()?
+    .from_file(path)
+    .await
+}
+
+/// Compress `input` to `output` with the key in the `TINIFY_KEY` environment variable.
+pub async fn compress<P, Q>(input: P, output: Q) -> Result<(), TinifyError>
+where
+  P: AsRef<Path>,
+  Q: AsRef<Path>,
+{
+  client_from_env()?
+    .from_file(input)
+    .await?
+    .to_file(output.as_ref().to_path_buf())
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+
+  #[tokio::test]
+  async fn test_from_file_missing() {
+    let request = from_file("invalid", "./missing.jpg").await;
+
+    assert_matches!(request, Err(TinifyError::IOError(_)));
+  }
+}
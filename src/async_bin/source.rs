@@ -1,214 +1,838 @@
+use crate::cache;
+use crate::classify_status;
+use crate::content_type;
 use crate::convert::Convert;
+use crate::convert::Type;
 use crate::error::TinifyError;
 use crate::error::Upstream;
+use crate::format::check_content;
+use crate::format::check_extension;
+use crate::format::check_output;
+use crate::format::check_source_type;
+use crate::format::detect_format;
+use crate::format::exif_orientation;
+use crate::format::has_alpha;
+use crate::format::image_size;
+use crate::format::is_animated;
+use crate::format::DetectedFormat;
+use crate::image_dimensions;
+use crate::location::check_credentials;
+use crate::location::source_url;
+use crate::location::stored_url;
+use crate::location::ResultUrl;
+use crate::parse_header;
+use crate::path_with_suffix;
+use crate::plan::RequestPlan;
+use crate::preserve::Preserve;
+use crate::read_file;
+use crate::redact_location;
+use crate::resize::Method;
 use crate::resize::Resize;
+use crate::retry_after;
+use crate::state::Empty;
+use crate::state::Ready;
+use crate::state::State;
+use crate::store::Store;
+use crate::suggested_file_name;
 use crate::transform::Transform;
+use crate::write_output;
+use crate::CompressionCount;
+use crate::Config;
 use crate::Operations;
 use crate::SourceUrl;
-use crate::API_ENDPOINT;
+use crate::StoreOperations;
+use crate::REQUEST_TIMEOUT;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+#[cfg(feature = "stream")]
+use bytes::Bytes;
+#[cfg(feature = "stream")]
+use futures_util::stream;
+#[cfg(feature = "stream")]
+use futures_util::Stream;
+#[cfg(feature = "stream")]
+use futures_util::StreamExt;
+#[cfg(feature = "stream")]
+use futures_util::TryStreamExt;
+use reqwest::header::HeaderMap;
 use reqwest::header::HeaderValue;
 use reqwest::header::CONTENT_TYPE;
+use reqwest::Body;
 use reqwest::Client as ReqwestClient;
+use reqwest::Response;
 use reqwest::StatusCode;
 use serde_json::json;
 use serde_json::Value;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::BufWriter;
-use std::io::Read;
-use std::io::Write;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
 use std::path::Path;
-use std::str;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 use tokio::task;
 use url::Url;
 
-#[derive(Debug)]
-pub struct Source {
-  key: Option<String>,
-  buffer: Option<Vec<u8>>,
-  output: Option<String>,
+/// The statuses a request of operations succeeds with, Tinify answers `200 OK`.
+const OPERATIONS_SUCCESS: [StatusCode; 3] =
+  [StatusCode::OK, StatusCode::CREATED, StatusCode::ACCEPTED];
+
+/// Pass a response with one of the `expected` statuses on, any other is turned into its error by `classify_status`, reading at most `limit` bytes of its body plus one to tell whether it was cut.
+async fn check_status(
+  mut response: Response,
+  expected: &[StatusCode],
+  limit: usize,
+) -> Result<Response, TinifyError> {
+  let status = response.status();
+  let retry_after = retry_after(response.headers());
+  let mut body = Vec::new();
+  if !expected.contains(&status) {
+    while let Some(chunk) = response.chunk().await? {
+      body.extend_from_slice(&chunk);
+      if body.len() > limit {
+        break;
+      }
+    }
+  }
+  classify_status(status, expected, retry_after, &body, limit)?;
+
+  Ok(response)
+}
+
+/// Pass a response with any `2xx` status on, like [`check_status`], for downloads where every success carries the image.
+async fn check_success(
+  response: Response,
+  limit: usize,
+) -> Result<Response, TinifyError> {
+  let status = response.status();
+  let expected = Some(status).filter(StatusCode::is_success);
+
+  check_status(response, expected.as_slice(), limit).await
+}
+
+/// A chosen image and its pending operations.
+///
+/// Clients only hand out [`Ready`] sources, so an image is always chosen before it can be compressed or saved. See [`state`](crate::state) for the states.
+#[must_use = "the operations only run when the image is saved with `to_file` or `to_buffer`"]
+pub struct Source<S: State = Ready> {
+  inner: Inner,
+  state: PhantomData<S>,
+}
+
+/// The fields of a [`Source`] in every state, moved whole from one state to the next.
+struct Inner {
+  key: Option<Arc<str>>,
+  buffer: Option<Arc<[u8]>>,
+  output: Option<ResultUrl>,
+  dimensions: Option<(u32, u32)>,
+  content_type: Option<String>,
+  input: Option<Vec<u8>>,
+  input_digest: Option<cache::Digest>,
+  input_size: Option<usize>,
+  input_orientation: Option<u8>,
+  input_dimensions: Option<(u32, u32)>,
+  input_format: Option<DetectedFormat>,
+  input_animated: bool,
+  input_transparent: bool,
+  upload_duration: Option<Duration>,
+  processing_duration: Option<Duration>,
+  download_duration: Option<Duration>,
+  response_headers: Option<HeaderMap>,
+  original: Option<Arc<[u8]>>,
+  kept_original: bool,
   reqwest_client: ReqwestClient,
   operations: Operations,
+  operations_set: bool,
+  config: Arc<Config>,
+  compression_count: CompressionCount,
 }
 
-impl Source {
-  pub(crate) fn new(key: Option<&str>) -> Self {
-    let key = key.map(|val| val.into());
-    let reqwest_client = ReqwestClient::new();
-    let operations = Operations {
-      convert: None,
-      resize: None,
-      transform: None,
-    };
+/// The key, the result URL and the image bytes are left out, so a source can be logged or shown in a panic message.
+impl<S: State> fmt::Debug for Source<S> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Source")
+      .field("key", &self.inner.key.as_ref().map(|_| "<redacted>"))
+      .field(
+        "buffer",
+        &self.inner.buffer.as_ref().map(|bytes| bytes.len()),
+      )
+      .field("output", &self.inner.output.as_ref().map(|_| "<redacted>"))
+      .field("dimensions", &self.inner.dimensions)
+      .field("content_type", &self.inner.content_type)
+      .field("input", &self.inner.input.as_ref().map(|bytes| bytes.len()))
+      .field("input_digest", &self.inner.input_digest)
+      .field("input_size", &self.inner.input_size)
+      .field("input_orientation", &self.inner.input_orientation)
+      .field("input_dimensions", &self.inner.input_dimensions)
+      .field("input_format", &self.inner.input_format)
+      .field("input_animated", &self.inner.input_animated)
+      .field("input_transparent", &self.inner.input_transparent)
+      .field("upload_duration", &self.inner.upload_duration)
+      .field("processing_duration", &self.inner.processing_duration)
+      .field("download_duration", &self.inner.download_duration)
+      .field(
+        "response_headers",
+        &self.inner.response_headers.as_ref().map(redact_location),
+      )
+      .field(
+        "original",
+        &self.inner.original.as_ref().map(|bytes| bytes.len()),
+      )
+      .field("kept_original", &self.inner.kept_original)
+      .field("reqwest_client", &self.inner.reqwest_client)
+      .field("operations", &self.inner.operations)
+      .field("operations_set", &self.inner.operations_set)
+      .field("config", &self.inner.config)
+      .field("compression_count", &self.inner.compression_count)
+      .finish()
+  }
+}
+
+impl Source<Empty> {
+  pub(crate) fn new(
+    key: Option<Arc<str>>,
+    reqwest_client: ReqwestClient,
+    config: Arc<Config>,
+    compression_count: CompressionCount,
+  ) -> Self {
+    let operations = config.default_operations.clone();
 
     Self {
-      key,
-      buffer: None,
-      output: None,
-      reqwest_client,
-      operations,
+      inner: Inner {
+        key,
+        buffer: None,
+        output: None,
+        dimensions: None,
+        content_type: None,
+        input: None,
+        input_digest: None,
+        input_size: None,
+        input_orientation: None,
+        input_dimensions: None,
+        input_format: None,
+        input_animated: false,
+        input_transparent: false,
+        upload_duration: None,
+        processing_duration: None,
+        download_duration: None,
+        response_headers: None,
+        original: None,
+        kept_original: false,
+        reqwest_client,
+        operations,
+        operations_set: false,
+        config,
+        compression_count,
+      },
+      state: PhantomData,
     }
   }
 
-  async fn get_source_from_response(
+  async fn get_source_from_response<B>(
     mut self,
-    buffer: Option<&[u8]>,
+    buffer: Option<B>,
     json: Option<Value>,
-  ) -> Result<Self, TinifyError> {
-    let parse = Url::parse(API_ENDPOINT)?;
-    let url = parse.join("/shrink")?;
-    let compressed_image = if let Some(json) = json {
-      self
-        .reqwest_client
-        .post(url)
-        .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
-        .body(json.to_string())
-        .basic_auth("api", self.key.as_ref())
-        .timeout(Duration::from_secs(300))
-        .send()
-        .await?
-    } else {
-      self
-        .reqwest_client
-        .post(url)
-        .body(buffer.unwrap().to_vec())
-        .basic_auth("api", self.key.as_ref())
-        .timeout(Duration::from_secs(300))
-        .send()
-        .await?
-    };
+  ) -> Result<Source, TinifyError>
+  where
+    B: AsRef<[u8]> + Into<Body>,
+  {
+    let input = buffer.as_ref().map(AsRef::as_ref);
 
-    match compressed_image.status() {
-      StatusCode::CREATED => {
-        if let Some(location) = compressed_image.headers().get("location") {
-          let location = location.to_str()?.to_string();
-          let bytes = self
-            .reqwest_client
-            .get(&location)
-            .timeout(Duration::from_secs(300))
-            .send()
-            .await?
-            .bytes()
-            .await?
-            .to_vec();
-
-          self.buffer = Some(bytes);
-          self.output = Some(location);
-
-          Ok(self)
-        } else {
-          let upstream = Upstream {
-            error: "Empty".to_string(),
-            message: "The location of the compressed image is empty."
-              .to_string(),
-          };
-          Err(TinifyError::ServerError { upstream })
-        }
-      }
-      StatusCode::UNAUTHORIZED | StatusCode::UNSUPPORTED_MEDIA_TYPE => {
-        let upstream: Upstream =
-          serde_json::from_str(&compressed_image.text().await?)?;
-        Err(TinifyError::ClientError { upstream })
+    if input.is_some_and(<[u8]>::is_empty) {
+      let upstream = Upstream {
+        error: "Empty".to_string(),
+        message: "The input is empty, there is no image to compress."
+          .to_string(),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+
+    self.inner.input_size = input.map(<[u8]>::len);
+    self.inner.input_orientation = input.and_then(exif_orientation);
+    self.inner.input_dimensions = input.and_then(image_size);
+    self.inner.input_format = input.and_then(detect_format);
+    self.inner.input_animated = input.is_some_and(is_animated);
+    self.inner.input_transparent = input.is_some_and(has_alpha);
+
+    if self.inner.config.reject_animated && self.inner.input_animated {
+      let upstream = Upstream {
+        error: "Animated".to_string(),
+        message: "Animated PNG and WebP images are not supported.".to_string(),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+
+    if self.inner.config.keep_original_if_larger {
+      self.inner.original = input.map(Arc::from);
+    }
+
+    if self.inner.config.dry_run {
+      self.inner.input = input.map(<[u8]>::to_vec);
+      return Ok(self.ready());
+    }
+
+    let cache_key = match (&self.inner.config.cache, input) {
+      (Some(cache), Some(input)) => {
+        let digest = cache::digest(input);
+        self.inner.input_digest = Some(digest);
+        Some(cache.key(digest, &Operations::default())?)
       }
-      _ => {
-        let upstream: Upstream =
-          serde_json::from_str(&compressed_image.text().await?)?;
-        Err(TinifyError::ServerError { upstream })
+      _ => None,
+    };
+
+    if let (Some(cache), Some(key)) = (&self.inner.config.cache, &cache_key) {
+      if let Some(bytes) = cache.get(key)? {
+        self.inner.content_type =
+          detect_format(&bytes).map(|format| format.mime_type().to_string());
+        self.inner.buffer = Some(bytes.into());
+        self.inner.input = input.map(<[u8]>::to_vec);
+        return Ok(self.ready());
       }
     }
+
+    self.shrink(buffer.map(Into::into), json).await?;
+
+    if let (Some(cache), Some(key), Some(bytes)) =
+      (&self.inner.config.cache, &cache_key, &self.inner.buffer)
+    {
+      cache.put(key, bytes)?;
+    }
+
+    Ok(self.ready())
   }
 
   #[allow(clippy::wrong_self_convention)]
   pub(crate) async fn from_buffer(
     self,
     buffer: &[u8],
-  ) -> Result<Self, TinifyError> {
-    self.get_source_from_response(Some(buffer), None).await
+  ) -> Result<Source, TinifyError> {
+    self
+      .get_source_from_response(Some(buffer.to_vec()), None)
+      .await
+  }
+
+  #[cfg(feature = "bytes")]
+  #[allow(clippy::wrong_self_convention)]
+  pub(crate) async fn from_bytes(
+    self,
+    bytes: bytes::Bytes,
+  ) -> Result<Source, TinifyError> {
+    self.get_source_from_response(Some(bytes), None).await
   }
 
   #[allow(clippy::wrong_self_convention)]
-  pub(crate) async fn from_file<P>(self, path: P) -> Result<Self, TinifyError>
+  pub(crate) async fn from_file<P, F>(
+    self,
+    path: P,
+    progress: F,
+  ) -> Result<Source, TinifyError>
   where
     P: AsRef<Path>,
+    F: FnMut(u64, u64),
   {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let mut buffer = Vec::with_capacity(reader.capacity());
-    reader.read_to_end(&mut buffer)?;
+    let path = path.as_ref();
+    if self.inner.config.reject_unsupported_extensions {
+      check_extension(path)?;
+    }
+
+    let buffer = read_file(path, progress)?;
+
+    if self.inner.config.reject_unsupported_extensions {
+      check_content(path, &buffer)?;
+    }
+
+    self.get_source_from_response(Some(buffer), None).await
+  }
+
+  #[cfg(feature = "svg")]
+  #[allow(clippy::wrong_self_convention)]
+  pub(crate) async fn from_svg(
+    self,
+    svg: &[u8],
+    width: u32,
+    height: u32,
+  ) -> Result<Source, TinifyError> {
+    let png = crate::svg::rasterize(svg, width, height)?;
+
+    self.get_source_from_response(Some(png), None).await
+  }
+
+  #[cfg(feature = "gif")]
+  #[allow(clippy::wrong_self_convention)]
+  pub(crate) async fn from_gif(
+    self,
+    gif: &[u8],
+  ) -> Result<Source, TinifyError> {
+    let png = crate::gif::first_frame(gif)?;
 
-    self.get_source_from_response(Some(&buffer), None).await
+    self.get_source_from_response(Some(png), None).await
   }
 
   #[allow(clippy::wrong_self_convention)]
-  pub(crate) async fn from_url<P>(self, path: P) -> Result<Self, TinifyError>
+  pub(crate) async fn from_url<P>(self, path: P) -> Result<Source, TinifyError>
   where
     P: AsRef<str> + Into<String>,
   {
+    let url = source_url(path.as_ref())?;
     let json = json!({
-      "source": SourceUrl { url: path.into() },
+      "source": SourceUrl { url: url.into() },
     });
 
-    self.get_source_from_response(None, Some(json)).await
+    self
+      .get_source_from_response(None::<Vec<u8>>, Some(json))
+      .await
+  }
+
+  /// Download the image from an origin protected by basic auth and upload it, the credentials are only sent to the origin.
+  #[allow(clippy::wrong_self_convention)]
+  pub(crate) async fn from_url_with_auth(
+    self,
+    url: &str,
+    user: &str,
+    password: &str,
+  ) -> Result<Source, TinifyError> {
+    let url = source_url(url)?;
+    check_credentials(user, password)?;
+
+    // The permit is released before the upload, which takes its own.
+    let buffer = {
+      let limiter = self.inner.config.concurrency.clone();
+      let _permit = match &limiter {
+        Some(limiter) => Some(limiter.acquire().await),
+        None => None,
+      };
+      let response = self
+        .inner
+        .reqwest_client
+        .get(url)
+        .basic_auth(user, Some(password))
+        .timeout(
+          self
+            .inner
+            .config
+            .source_fetch_timeout
+            .unwrap_or(REQUEST_TIMEOUT),
+        )
+        .send()
+        .await?;
+      let status = response.status();
+      if !status.is_success() {
+        let upstream = Upstream {
+          error: "Source".to_string(),
+          message: format!("The origin answered {}.", status),
+        };
+        return Err(TinifyError::ClientError { upstream });
+      }
+      let content_type = content_type(response.headers());
+      let bytes = response.bytes().await?;
+      check_source_type(content_type.as_deref(), &bytes)?;
+      bytes
+    };
+
+    self
+      .get_source_from_response(Some(buffer.to_vec()), None)
+      .await
+  }
+
+  /// Move on to the [`Ready`] state once the image was chosen.
+  fn ready(self) -> Source {
+    Source {
+      inner: self.inner,
+      state: PhantomData,
+    }
+  }
+}
+
+/// Warn when a source with an uploaded image is dropped before the operations set on it ran, they only run in `to_file`, `to_buffer` or `apply`. The default operations of the builder alone don't warn. A change of state moves the fields whole, so only dropping the source itself warns.
+#[cfg(feature = "tracing")]
+impl Drop for Inner {
+  fn drop(&mut self) {
+    if self.buffer.is_some() && self.operations_set {
+      tracing::warn!(
+        operations = %self.operations,
+        "the source was dropped before its pending operations ran",
+      );
+    }
+  }
+}
+
+impl<S: State> Source<S> {
+  async fn shrink(
+    &mut self,
+    body: Option<Body>,
+    json: Option<Value>,
+  ) -> Result<(), TinifyError> {
+    let limiter = self.inner.config.concurrency.clone();
+    let _permit = match &limiter {
+      Some(limiter) => Some(limiter.acquire().await),
+      None => None,
+    };
+    let endpoint = self.inner.config.endpoint()?;
+    let url = endpoint.join("/shrink")?;
+    let started = Instant::now();
+    let compressed_image = match (json, body) {
+      (Some(json), _) => {
+        self
+          .inner
+          .reqwest_client
+          .post(url)
+          .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+          .body(json.to_string())
+          .basic_auth("api", self.inner.key.as_ref())
+          .timeout(
+            self
+              .inner
+              .config
+              .source_fetch_timeout
+              .unwrap_or(REQUEST_TIMEOUT),
+          )
+          .send()
+          .await?
+      }
+      (None, Some(body)) => {
+        self
+          .inner
+          .reqwest_client
+          .post(url)
+          .body(body)
+          .basic_auth("api", self.inner.key.as_ref())
+          .timeout(REQUEST_TIMEOUT)
+          .send()
+          .await?
+      }
+      (None, None) => {
+        let upstream = Upstream {
+          error: "Source".to_string(),
+          message: "No source set, choose an image with `from_file`, `from_buffer` or `from_url` first.".to_string(),
+        };
+        return Err(TinifyError::ClientError { upstream });
+      }
+    };
+
+    self.inner.upload_duration = Some(started.elapsed());
+    self
+      .inner
+      .compression_count
+      .update(compressed_image.headers());
+
+    let limit = self.inner.config.error_body_limit();
+    let compressed_image =
+      check_status(compressed_image, &[StatusCode::CREATED], limit).await?;
+
+    if let Some(location) = compressed_image.headers().get("location") {
+      let location = ResultUrl::parse(location.to_str()?, &endpoint)?;
+      let response = self
+        .inner
+        .reqwest_client
+        .get(location.as_url().clone())
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+      let response = check_success(response, limit).await?;
+
+      let dimensions = image_dimensions(response.headers());
+      let content_type = content_type(response.headers());
+      self.inner.compression_count.update(response.headers());
+      self.inner.response_headers = Some(response.headers().clone());
+      let started = Instant::now();
+      let bytes = response.bytes().await?.to_vec();
+      self.inner.download_duration = Some(started.elapsed());
+      self.verify(&bytes)?;
+
+      self.inner.buffer = Some(bytes.into());
+      self.inner.dimensions = dimensions;
+      self.inner.content_type = content_type;
+      self.inner.output = Some(location);
+
+      Ok(())
+    } else {
+      let upstream = Upstream {
+        error: "Empty".to_string(),
+        message: "The location of the compressed image is empty.".to_string(),
+      };
+      Err(TinifyError::ServerError {
+        upstream,
+        status: None,
+      })
+    }
+  }
+
+  /// Check a downloaded result when `Tinify::verify_output` is set.
+  fn verify(&self, bytes: &[u8]) -> Result<(), TinifyError> {
+    if self.inner.config.verify_output {
+      check_output(bytes)?;
+    }
+
+    Ok(())
   }
+}
 
+impl Source {
   /// Resize the current compressed image.
+  #[must_use = "the operation only runs when the image is saved"]
   pub fn resize(mut self, resize: Resize) -> Result<Self, TinifyError> {
-    self.operations.resize = Some(resize);
+    self.inner.operations.resize = Some(resize);
+    self.inner.operations_set = true;
     Ok(self)
   }
 
   /// Convert the current compressed image.
+  ///
+  /// The target may be the type of the input, see [`Convert`] for how that differs from the default compression.
+  #[must_use = "the operation only runs when the image is saved"]
   pub fn convert(mut self, convert: Convert) -> Result<Self, TinifyError> {
-    self.operations.convert = Some(convert);
+    self.inner.operations.convert = Some(convert);
+    self.inner.operations_set = true;
+    Ok(self)
+  }
+
+  /// Convert the current compressed image right away and report which type was chosen.
+  ///
+  /// With more than one type Tinify returns the smallest result, the chosen type is read from the `Content-Type` of the response.
+  pub async fn convert_and_report(
+    mut self,
+    convert: Convert,
+  ) -> Result<(Self, Type), TinifyError> {
+    self.inner.operations.convert = Some(convert);
+    self.inner.operations_set = true;
+    self.run_operations().await?;
+
+    let r#type = match self.inner.content_type.as_deref() {
+      Some(content_type) => Type::try_from(content_type)?,
+      None => {
+        let upstream = Upstream {
+          error: "Type".to_string(),
+          message: "The type of the converted image is unknown.".to_string(),
+        };
+        return Err(TinifyError::ServerError {
+          upstream,
+          status: None,
+        });
+      }
+    };
+
+    Ok((self, r#type))
+  }
+
+  /// Add an operation the crate has no typed method for yet, by its name in the API and its JSON value.
+  ///
+  /// Setting the same name again replaces its value. The names of the typed operations
+  /// and `store` fail with a `ClientError` whose error is `Operation`.
+  #[must_use = "the operation only runs when the image is saved"]
+  pub fn raw_operation(
+    mut self,
+    name: &str,
+    value: Value,
+  ) -> Result<Self, TinifyError> {
+    self.inner.operations.set_raw(name, value)?;
+    self.inner.operations_set = true;
+    Ok(self)
+  }
+
+  /// Run the pending operations now, so the next ones apply to their result instead of joining the same request.
+  ///
+  /// Operations chained without `apply` are sent together to the uploaded image, which costs the shrink plus one compression for `resize(..)?.convert(..)`. After `apply` the result is uploaded again before the next operations, so `resize(..)?.apply().await?.convert(..)` costs the shrink, the resize, a second shrink and the convert.
+  pub async fn apply(mut self) -> Result<Self, TinifyError> {
+    if !self.inner.operations.is_empty() {
+      self.run_operations().await?;
+      self.inner.output = None;
+      self.inner.input_transparent =
+        self.inner.buffer.as_deref().is_some_and(has_alpha);
+      self.inner.input_dimensions =
+        self.inner.buffer.as_deref().and_then(image_size);
+      self.inner.input_digest =
+        match (&self.inner.config.cache, &self.inner.buffer) {
+          (Some(_), Some(buffer)) => Some(cache::digest(buffer)),
+          _ => None,
+        };
+      self.inner.input = self.inner.buffer.as_deref().map(<[u8]>::to_vec);
+    }
+
     Ok(self)
   }
 
   /// Transform the current compressed image.
+  #[must_use = "the operation only runs when the image is saved"]
   pub fn transform(
     mut self,
     transform: Transform,
   ) -> Result<Self, TinifyError> {
-    self.operations.transform = Some(transform);
+    self.inner.operations.transform = Some(transform);
+    self.inner.operations_set = true;
+    Ok(self)
+  }
+
+  /// Preserve metadata of the uploaded image in the current compressed image.
+  #[must_use = "the operation only runs when the image is saved"]
+  pub fn preserve(mut self, preserve: Preserve) -> Result<Self, TinifyError> {
+    self.inner.operations.preserve = Some(preserve);
+    self.inner.operations_set = true;
     Ok(self)
   }
 
+  /// Create a resized and converted variant of the current compressed image for every width, as used in a `srcset`.
+  ///
+  /// The other pending operations apply to every variant, a pending resize or convert is replaced by the width and `format` of each. Every variant is checked like saving before any is requested, then the pending operations are cleared, even when one fails. Each variant is a separate request and counts as an additional compression.
+  pub async fn responsive_set(
+    &mut self,
+    widths: &[u32],
+    format: Type,
+  ) -> Result<Vec<(u32, Vec<u8>)>, TinifyError> {
+    self.check_dry_run()?;
+
+    if self.inner.output.is_none() {
+      if let Some(input) = self.inner.input.take() {
+        self.shrink(Some(input.into()), None).await?;
+      }
+    }
+
+    let requests = widths
+      .iter()
+      .map(|&width| {
+        let operations = Operations {
+          convert: Some(Convert {
+            r#type: vec![format.clone()],
+          }),
+          resize: Some(Resize {
+            method: Method::Scale,
+            width: Some(width),
+            height: None,
+          }),
+          ..self.inner.operations.clone()
+        };
+        Ok((width, self.validate(operations)?))
+      })
+      .collect::<Result<Vec<_>, TinifyError>>()?;
+    // The pending operations are sent from here on, even if a variant fails.
+    self.inner.operations = Operations::default();
+    self.inner.operations_set = false;
+    let mut variants = Vec::with_capacity(widths.len());
+
+    for (width, operations) in requests {
+      let limiter = self.inner.config.concurrency.clone();
+      let _permit = match &limiter {
+        Some(limiter) => Some(limiter.acquire().await),
+        None => None,
+      };
+      let response = self.post_operations(&operations, None).await?;
+      let bytes = response.bytes().await?.to_vec();
+      self.verify(&bytes)?;
+
+      variants.push((width, bytes));
+    }
+
+    Ok(variants)
+  }
+
+  /// Send every pending operation in a single request to the output url.
+  ///
+  /// Chaining `resize`, `convert`, `transform` and `preserve` costs the shrink plus this one request, not a round trip per operation.
   async fn run_operations(&mut self) -> Result<(), TinifyError> {
-    let operations = serde_json::to_string(&self.operations)?;
+    self.check_dry_run()?;
+    self.inner.operations = self.validate(self.inner.operations.clone())?;
+    self.inner.original = None;
+
+    let cache_key = match (&self.inner.config.cache, self.inner.input_digest) {
+      (Some(cache), Some(digest)) => {
+        Some(cache.key(digest, &self.inner.operations)?)
+      }
+      _ => None,
+    };
+
+    if let (Some(cache), Some(key)) = (&self.inner.config.cache, &cache_key) {
+      if let Some(bytes) = cache.get(key)? {
+        // A cached result has no headers, its type is told from its bytes.
+        self.inner.content_type =
+          detect_format(&bytes).map(|format| format.mime_type().to_string());
+        self.inner.buffer = Some(bytes.into());
+        self.inner.response_headers = None;
+        self.inner.operations = Operations::default();
+        self.inner.operations_set = false;
+        return Ok(());
+      }
+    }
+
+    if self.inner.output.is_none() {
+      if let Some(input) = self.inner.input.take() {
+        self.shrink(Some(input.into()), None).await?;
+      }
+    }
+
+    let limiter = self.inner.config.concurrency.clone();
+    let _permit = match &limiter {
+      Some(limiter) => Some(limiter.acquire().await),
+      None => None,
+    };
+    // The pending operations are sent from here on, even if the request fails.
+    let operations = mem::take(&mut self.inner.operations);
+    self.inner.operations_set = false;
+    let started = Instant::now();
+    let response = self.post_operations(&operations, None).await?;
+    self.inner.processing_duration = Some(started.elapsed());
+    let dimensions = image_dimensions(response.headers());
+    let content_type = content_type(response.headers());
+    self.inner.response_headers = Some(response.headers().clone());
+    let started = Instant::now();
+    let bytes = response.bytes().await?.to_vec();
+    self.inner.download_duration = Some(started.elapsed());
+    self.verify(&bytes)?;
+
+    if let (Some(cache), Some(key)) = (&self.inner.config.cache, &cache_key) {
+      cache.put(key, &bytes)?;
+    }
 
-    if let Some(output) = self.output.take() {
+    self.inner.buffer = Some(bytes.into());
+    self.inner.dimensions = dimensions;
+    self.inner.content_type = content_type;
+
+    Ok(())
+  }
+
+  async fn post_operations(
+    &self,
+    operations: &Operations,
+    store: Option<&Store>,
+  ) -> Result<Response, TinifyError> {
+    let body = match store {
+      Some(store) => {
+        serde_json::to_string(&StoreOperations { operations, store })?
+      }
+      None => serde_json::to_string(operations)?,
+    };
+
+    if let Some(output) = self.inner.output.as_ref() {
       let response = self
+        .inner
         .reqwest_client
-        .post(output)
+        .post(output.as_url().clone())
         .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
-        .body(operations)
-        .basic_auth("api", self.key.as_ref())
-        .timeout(Duration::from_secs(300))
+        .body(body)
+        .basic_auth("api", self.inner.key.as_ref())
+        .timeout(REQUEST_TIMEOUT)
         .send()
         .await?;
 
-      match response.status() {
-        StatusCode::OK => {
-          let bytes = response.bytes().await?.to_vec();
-
-          self.buffer = Some(bytes);
-
-          Ok(())
-        }
-        StatusCode::BAD_REQUEST
-        | StatusCode::UNAUTHORIZED
-        | StatusCode::UNSUPPORTED_MEDIA_TYPE => {
-          let upstream: Upstream =
-            serde_json::from_str(&response.text().await?)?;
-          Err(TinifyError::ClientError { upstream })
-        }
-        StatusCode::SERVICE_UNAVAILABLE => {
-          let upstream: Upstream =
-            serde_json::from_str(&response.text().await?)?;
-          Err(TinifyError::ServerError { upstream })
-        }
-        _ => unreachable!(),
+      self.inner.compression_count.update(response.headers());
+
+      let checked = check_status(
+        response,
+        &OPERATIONS_SUCCESS,
+        self.inner.config.error_body_limit(),
+      )
+      .await;
+      // Only the operations are logged, the key is sent as basic auth and the store holds credentials.
+      #[cfg(feature = "tracing")]
+      if let Err(TinifyError::ClientError { upstream }) = &checked {
+        tracing::warn!(
+          error = %upstream.error,
+          message = %upstream.message,
+          operations = %serde_json::to_string(operations)?,
+          "the API rejected the operations",
+        );
       }
+
+      checked
     } else {
       let upstream = Upstream {
         error: "Empty".to_string(),
@@ -218,45 +842,869 @@ impl Source {
     }
   }
 
+  /// Describe the compressed image for the sidecar of `save_both`.
+  fn sidecar(&self, operations: String) -> Value {
+    let seconds = |duration: Option<Duration>| {
+      duration.map(|duration| duration.as_secs_f64())
+    };
+
+    json!({
+      "input_size": self.inner.input_size,
+      "output_size": self.inner.buffer.as_deref().map(<[u8]>::len),
+      "width": self.inner.dimensions.map(|(width, _)| width),
+      "height": self.inner.dimensions.map(|(_, height)| height),
+      "content_type": self.inner.content_type,
+      "url": self.inner.output.as_ref().map(ToString::to_string),
+      "operations": operations,
+      "upload_duration": seconds(self.inner.upload_duration),
+      "processing_duration": seconds(self.inner.processing_duration),
+      "download_duration": seconds(self.inner.download_duration),
+    })
+  }
+
+  /// Get the type and size in bytes of every candidate of the last convert.
+  ///
+  /// Tinify only returns the smallest candidate, so this holds at most that one,
+  /// and is empty until the image was downloaded.
+  pub fn convert_candidates(&self) -> Vec<(Type, usize)> {
+    let r#type = self
+      .inner
+      .content_type
+      .as_deref()
+      .and_then(|content_type| Type::try_from(content_type).ok());
+
+    match (r#type, &self.inner.buffer) {
+      (Some(r#type), Some(buffer)) => vec![(r#type, buffer.len())],
+      _ => Vec::new(),
+    }
+  }
+
+  /// Validate the pending operations and serialize them, as they are sent to the API.
+  pub fn request_body(&self) -> Result<String, TinifyError> {
+    let operations = self.validate(self.inner.operations.clone())?;
+
+    Ok(serde_json::to_string(&operations)?)
+  }
+
+  /// Describe what compressing this source will send to Tinify, for logs and support tickets, after the same validation as [`request_body`](Self::request_body).
+  ///
+  /// The plan never includes the key, serialize it with `serde_json` to share it. It works in a dry run too.
+  pub fn request_plan(&self) -> Result<RequestPlan, TinifyError> {
+    let operations = self.validate(self.inner.operations.clone())?;
+
+    RequestPlan::new(
+      &self.inner.config,
+      self.inner.output.as_ref(),
+      &operations,
+    )
+  }
+
+  /// Run the client side validation of `operations` against the input, returning them as they are sent, with the default background filled in.
+  fn validate(
+    &self,
+    operations: Operations,
+  ) -> Result<Operations, TinifyError> {
+    let operations =
+      operations.with_default_background(self.inner.config.default_background);
+    operations.check_resize()?;
+    operations.check_background(self.inner.input_transparent)?;
+
+    if self.inner.config.reject_upscale {
+      operations.check_upscale(
+        self.inner.input_dimensions,
+        self.inner.input_orientation,
+      )?;
+    }
+
+    Ok(operations)
+  }
+
+  /// Fail with the plan of the requests that would have been sent, in a dry run.
+  fn check_dry_run(&self) -> Result<(), TinifyError> {
+    if self.inner.config.dry_run {
+      return Err(TinifyError::DryRun(Box::new(self.request_plan()?)));
+    }
+
+    Ok(())
+  }
+
+  /// Check whether the uploaded image can be converted to `r#type`, judging from its detected format.
+  ///
+  /// This is conservative guidance for UIs, it only returns `false` when the conversion is known to fail: a GIF, bytes that are not an image at all, or an animated PNG or WebP to any other type than its own. Images compressed from an url are not inspected, the API decides for them. Converting a transparent image to JPEG also needs a [`Transform`] background.
+  pub fn can_convert_to(&self, r#type: Type) -> bool {
+    match (self.inner.input_format, r#type) {
+      (Some(DetectedFormat::Gif), _) => false,
+      (Some(DetectedFormat::Png), Type::Png)
+      | (Some(DetectedFormat::Webp), Type::Webp) => true,
+      (Some(_), _) => !self.inner.input_animated,
+      (None, _) => self.inner.input_size.is_none(),
+    }
+  }
+
+  /// Discard the pending operations, back to the default operations of the client, so other operations can run against the same upload.
+  ///
+  /// Operations always run against the uploaded image kept at Tinify, not against the result of earlier ones, unless [`apply`](Self::apply) uploaded that result. Generating variants this way uploads once: the shrink is one compression, and every save that runs operations is one more, whatever they combine. Saving without new operations still gives the last result.
+  pub fn reset_operations(&mut self) {
+    self.inner.operations = self.inner.config.default_operations.clone();
+    self.inner.operations_set = false;
+  }
+
+  /// Summarize the pending operations for logs, like `resize(fit 400x200) + convert(webp)`, or `none`.
+  pub fn operations_summary(&self) -> String {
+    self.inner.operations.to_string()
+  }
+
+  /// Get the url of the compressed image on the Tinify servers, `None` before the image was uploaded.
+  pub fn url(&self) -> Option<&ResultUrl> {
+    self.inner.output.as_ref()
+  }
+
+  /// Get the `Content-Type` of the compressed image.
+  ///
+  /// A result from the cache has no response, its type is detected from its bytes instead.
+  pub fn content_type(&self) -> Option<&str> {
+    self.inner.content_type.as_deref()
+  }
+
+  /// Get the `Content-Type` of the compressed image as a [`Mime`](mime::Mime), `None` when it is missing or invalid.
+  #[cfg(feature = "mime")]
+  pub fn mime_type(&self) -> Option<mime::Mime> {
+    self.inner.content_type.as_deref()?.parse().ok()
+  }
+
+  /// Get the format of the uploaded image, detected from its magic bytes.
+  ///
+  /// `None` when compressing from an url and when the bytes are not a known image format.
+  pub fn input_format(&self) -> Option<DetectedFormat> {
+    self.inner.input_format
+  }
+
+  /// Get the format of the current compressed image, detected from its magic bytes or else from its `Content-Type`.
+  ///
+  /// With [`input_format`](Self::input_format) this tells what a convert did, like `png -> webp`. `None` before the image was downloaded and when neither tells a known format.
+  pub fn output_format(&self) -> Option<DetectedFormat> {
+    self
+      .inner
+      .buffer
+      .as_deref()
+      .and_then(detect_format)
+      .or_else(|| {
+        self
+          .inner
+          .content_type
+          .as_deref()
+          .and_then(DetectedFormat::from_mime_type)
+      })
+  }
+
+  /// Get the size in bytes of the uploaded image, `None` when compressing from an url.
+  pub fn input_size(&self) -> Option<usize> {
+    self.inner.input_size
+  }
+
+  /// Get the EXIF orientation of the input image, from `1` (upright) to `8`.
+  ///
+  /// A [`Resize`] applies to the pixels as stored, orientations `5` to `8` are shown rotated
+  /// by 90 degrees. `None` without an orientation and for [`from_url`](crate::async_bin::Client::from_url).
+  pub fn input_orientation(&self) -> Option<u8> {
+    self.inner.input_orientation
+  }
+
+  /// Get the `(width, height)` of the compressed image reported by Tinify.
+  ///
+  /// Once the operations ran this is the size actually produced, which for a [`Method::Fit`](crate::resize::Method::Fit) resize can be smaller than requested.
+  pub fn dimensions(&self) -> Option<(u32, u32)> {
+    self.inner.dimensions
+  }
+
+  /// Get the `(width, height)` like [`dimensions`](Self::dimensions), failing with a `ParseIntError` when the `Image-Width` or `Image-Height` header of the download isn't a number.
+  ///
+  /// `dimensions` ignores such a header, this strict variant tells a misbehaving proxy apart from a missing header. `None` when a header is missing, before the image was downloaded and when it came from the cache.
+  pub fn checked_dimensions(&self) -> Result<Option<(u32, u32)>, TinifyError> {
+    match self.inner.response_headers.as_ref() {
+      Some(headers) => {
+        let width = parse_header(headers, "image-width")?;
+        let height = parse_header(headers, "image-height")?;
+        Ok(width.zip(height))
+      }
+      None => Ok(None),
+    }
+  }
+
+  /// Get the `Compression-Count` of the download of the current compressed image, failing with a `ParseIntError` when it isn't a number.
+  ///
+  /// The lenient `last_compression_count` of the client skips such a header. `None` when the header is missing, before the image was downloaded and when it came from the cache.
+  pub fn checked_compression_count(&self) -> Result<Option<u32>, TinifyError> {
+    match self.inner.response_headers.as_ref() {
+      Some(headers) => parse_header(headers, "compression-count"),
+      None => Ok(None),
+    }
+  }
+
+  /// Get how long the upload to `/shrink` took, from sending the image until the response headers with the compressed image's location arrived.
+  ///
+  /// Tinify compresses the image before it answers, so this includes the compression itself. `None` until the image was uploaded, also when it came from the cache.
+  pub fn upload_duration(&self) -> Option<Duration> {
+    self.inner.upload_duration
+  }
+
+  /// Get how long Tinify took to run the operations, from sending them until the response headers arrived.
+  ///
+  /// `None` until the operations ran.
+  pub fn processing_duration(&self) -> Option<Duration> {
+    self.inner.processing_duration
+  }
+
+  /// Get how long reading the body of the last download of the compressed image took, from the response headers until its last byte.
+  ///
+  /// `None` until an image was downloaded.
+  pub fn download_duration(&self) -> Option<Duration> {
+    self.inner.download_duration
+  }
+
+  /// Get the headers of the response the current compressed image was downloaded from, `None` before it was downloaded or when it came from the cache.
+  ///
+  /// This is the raw access for debugging a proxy or a CDN, or for headers the crate doesn't read itself. The key is sent with the request, as basic auth, so it is never part of these headers.
+  pub fn response_headers(&self) -> Option<&HeaderMap> {
+    self.inner.response_headers.as_ref()
+  }
+
+  /// Get whether the last `to_file` wrote the original bytes, because the compressed image was not smaller and `Tinify::keep_original_if_larger` is set.
+  pub fn kept_original(&self) -> bool {
+    self.inner.kept_original
+  }
+
   /// Save the current compressed image to a file.
+  ///
+  /// The image is written to a temporary file next to `path` and renamed once complete, so a failed write never leaves a partial file and keeps an existing one untouched.
+  ///
+  /// A missing parent directory fails with an IO error, unless directories are created with `Tinify::create_dirs`. With `Tinify::keep_original_if_larger` the original is written instead when compressing didn't make it smaller. With `Tinify::overwrite(false)` an existing file at `path` fails with an `AlreadyExists` IO error and is left untouched.
   pub async fn to_file<P>(&mut self, path: P) -> Result<(), TinifyError>
   where
     P: AsRef<Path> + Send + 'static,
   {
-    if self.operations.convert.is_some()
-      || self.operations.resize.is_some()
-      || self.operations.transform.is_some()
-    {
+    if self.inner.config.dry_run || !self.inner.operations.is_empty() {
       self.run_operations().await?;
     }
 
-    if let Some(ref buffer) = self.buffer {
-      let file = task::spawn_blocking(move || File::create(path)).await??;
-      let mut reader = BufWriter::new(file);
-      reader.write_all(buffer)?;
-      reader.flush()?;
+    self.inner.kept_original = match (&self.inner.original, &self.inner.buffer)
+    {
+      (Some(original), Some(buffer)) => original.len() <= buffer.len(),
+      _ => false,
+    };
+    let config = self.inner.config.clone();
+    let slot = if self.inner.kept_original {
+      &self.inner.original
+    } else {
+      &self.inner.buffer
+    };
+
+    // The task writes a shared handle, the source keeps the image even when the write is dropped halfway.
+    if let Some(buffer) = slot.clone() {
+      task::spawn_blocking(move || {
+        write_output(&config, path.as_ref(), &buffer)
+      })
+      .await??;
     }
 
     Ok(())
   }
 
+  /// Get the file name suggested by the `Content-Disposition` header of the download of the current compressed image.
+  ///
+  /// Tinify doesn't always send one, a proxy or a CDN in front of it may. `None` without the header, before the image was downloaded, and for a name with a directory in it.
+  pub fn suggested_file_name(&self) -> Option<String> {
+    self
+      .inner
+      .response_headers
+      .as_ref()
+      .and_then(suggested_file_name)
+  }
+
+  /// Save the compressed image in `dir` under the [`suggested_file_name`](Self::suggested_file_name), returning the path it was saved to.
+  ///
+  /// The pending operations run first, the name is suggested for their result. Without a suggested name this fails with a `ClientError` whose error is `Filename`, and nothing is written.
+  pub async fn to_file_suggested_name<P>(
+    &mut self,
+    dir: P,
+  ) -> Result<PathBuf, TinifyError>
+  where
+    P: AsRef<Path>,
+  {
+    if self.inner.config.dry_run || !self.inner.operations.is_empty() {
+      self.run_operations().await?;
+    }
+
+    let path = match self.suggested_file_name() {
+      Some(name) => dir.as_ref().join(name),
+      None => {
+        let upstream = Upstream {
+          error: "Filename".to_string(),
+          message:
+            "The server didn't suggest a file name for the compressed image."
+              .to_string(),
+        };
+        return Err(TinifyError::ClientError { upstream });
+      }
+    };
+    self.to_file(path.clone()).await?;
+
+    Ok(path)
+  }
+
+  /// Save the compressed image to `path` and a JSON sidecar describing it next to it, returning the path of the sidecar.
+  ///
+  /// The sidecar is `path` with `.json` appended, like `optimized.png.json`. It records the input and output sizes, the dimensions, the type, the result url, the operations and the durations of the requests, for build systems and audits that track every asset. The image is written first, both atomically.
+  pub async fn save_both<P>(&mut self, path: P) -> Result<PathBuf, TinifyError>
+  where
+    P: AsRef<Path>,
+  {
+    let operations = self.operations_summary();
+    let path = path.as_ref().to_path_buf();
+    self.to_file(path.clone()).await?;
+    let sidecar = path_with_suffix(&path, ".json");
+    let json = serde_json::to_vec_pretty(&self.sidecar(operations))?;
+    let written = sidecar.clone();
+    let config = self.inner.config.clone();
+    task::spawn_blocking(move || write_output(&config, &written, &json))
+      .await??;
+
+    Ok(sidecar)
+  }
+
+  /// Store the compressed image, with the pending operations applied, in a cloud bucket instead of downloading it, returning the url it was stored at.
+  ///
+  /// The response of a store has no image, so nothing is downloaded, cached or verified, and the current compressed image stays as it was. The pending operations are sent with the [`Store`] in one request and cleared once sent, even when the request fails. In a dry run this fails like saving.
+  pub async fn store(&mut self, store: Store) -> Result<Url, TinifyError> {
+    self.check_dry_run()?;
+    let operations = self.validate(self.inner.operations.clone())?;
+
+    if self.inner.output.is_none() {
+      if let Some(input) = self.inner.input.take() {
+        self.shrink(Some(input.into()), None).await?;
+      }
+    }
+
+    let limiter = self.inner.config.concurrency.clone();
+    let _permit = match &limiter {
+      Some(limiter) => Some(limiter.acquire().await),
+      None => None,
+    };
+    // The pending operations are sent from here on, even if the request fails.
+    self.inner.operations = Operations::default();
+    self.inner.operations_set = false;
+    let started = Instant::now();
+    let response = self.post_operations(&operations, Some(&store)).await?;
+    self.inner.processing_duration = Some(started.elapsed());
+
+    stored_url(response.headers())
+  }
+
   /// Save the current compressed image to a buffer.
   pub async fn to_buffer(&mut self) -> Result<Vec<u8>, TinifyError> {
-    if self.operations.convert.is_some()
-      || self.operations.resize.is_some()
-      || self.operations.transform.is_some()
-    {
+    let mut buffer = Vec::new();
+    self.write_to_vec(&mut buffer).await?;
+
+    Ok(buffer)
+  }
+
+  /// Append the current compressed image to an existing buffer.
+  pub async fn write_to_vec(
+    &mut self,
+    buf: &mut Vec<u8>,
+  ) -> Result<(), TinifyError> {
+    buf.extend_from_slice(self.compressed().await?);
+
+    Ok(())
+  }
+
+  /// Encode the current compressed image as standard base64.
+  pub async fn to_base64(&mut self) -> Result<String, TinifyError> {
+    Ok(STANDARD.encode(self.compressed().await?))
+  }
+
+  /// Encode the current compressed image as a data URI like `data:image/webp;base64,...`, for inlining small images in HTML or CSS.
+  pub async fn to_data_uri(&mut self) -> Result<String, TinifyError> {
+    let base64 = self.to_base64().await?;
+    let content_type = match self.inner.content_type.as_deref() {
+      Some(content_type) => content_type,
+      None => self
+        .inner
+        .buffer
+        .as_deref()
+        .and_then(detect_format)
+        .map_or("application/octet-stream", DetectedFormat::mime_type),
+    };
+
+    Ok(format!("data:{};base64,{}", content_type, base64))
+  }
+
+  /// Run the pending operations and take the image [`to_file`](Self::to_file) would write, the original with `Tinify::keep_original_if_larger` when compressing didn't make it smaller.
+  pub(crate) async fn saved_output(&mut self) -> Result<Vec<u8>, TinifyError> {
+    let size = self.compressed().await?.len();
+    let original = self
+      .inner
+      .original
+      .as_ref()
+      .filter(|original| original.len() <= size)
+      .map(|original| original.to_vec());
+    self.inner.kept_original = original.is_some();
+
+    match original {
+      Some(original) => Ok(original),
+      None => self.to_buffer().await,
+    }
+  }
+
+  /// Run the pending operations and borrow the resulting image.
+  async fn compressed(&mut self) -> Result<&[u8], TinifyError> {
+    if self.inner.config.dry_run || !self.inner.operations.is_empty() {
       self.run_operations().await?;
     }
 
-    if let Some(buffer) = self.buffer.as_ref() {
-      Ok(buffer.to_vec())
-    } else {
+    self.inner.buffer.as_deref().ok_or_else(|| {
       let upstream = Upstream {
         error: "Empty".to_string(),
         message: "Buffer of the compressed image is empty.".to_string(),
       };
+      TinifyError::ClientError { upstream }
+    })
+  }
+
+  /// Stream the compressed image instead of holding the result of the operations in memory, for very large outputs.
+  ///
+  /// Pending operations run with their response yielded chunk by chunk as it downloads, and the result is not written to the cache. Without operations the image compressed on upload is yielded as a single chunk.
+  #[cfg(feature = "stream")]
+  pub async fn into_byte_stream(
+    mut self,
+  ) -> Result<
+    impl Stream<Item = Result<Bytes, TinifyError>> + Send + 'static,
+    TinifyError,
+  > {
+    self.check_dry_run()?;
+
+    if self.inner.operations.is_empty() {
+      let buffer = self.inner.buffer.take().ok_or_else(|| {
+        let upstream = Upstream {
+          error: "Empty".to_string(),
+          message: "Buffer of the compressed image is empty.".to_string(),
+        };
+        TinifyError::ClientError { upstream }
+      })?;
+
+      let bytes = Bytes::from(buffer.to_vec());
+      return Ok(stream::once(async { Ok(bytes) }).boxed());
+    }
+
+    self.inner.operations = self.validate(self.inner.operations.clone())?;
+
+    if self.inner.output.is_none() {
+      if let Some(input) = self.inner.input.take() {
+        self.shrink(Some(input.into()), None).await?;
+      }
+    }
+
+    let limiter = self.inner.config.concurrency.clone();
+    let _permit = match &limiter {
+      Some(limiter) => Some(limiter.acquire().await),
+      None => None,
+    };
+    // The pending operations are sent from here on, even if the request fails.
+    let operations = mem::take(&mut self.inner.operations);
+    self.inner.operations_set = false;
+    let response = self.post_operations(&operations, None).await?;
+
+    Ok(response.bytes_stream().map_err(TinifyError::from).boxed())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::seed_cache;
+  use crate::fixture::tiny_png;
+  use crate::fixture::tiny_rgba_png;
+  use crate::mock;
+  use crate::mock::Reply;
+  use assert_matches::assert_matches;
+  use std::env;
+  use std::fs;
+  use std::thread;
+
+  #[tokio::test]
+  async fn test_status_of_operations() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+      Reply::new("200 OK").body(b"ok"),
+      Reply::new("201 Created").body(b"created"),
+      Reply::new("202 Accepted").body(b"accepted"),
+      Reply::new("500 Internal Server Error").body(b"upstream timed out"),
+      Reply::new("204 No Content"),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+    let resize = Resize {
+      method: Method::Scale,
+      width: Some(400),
+      height: None,
+    };
+
+    for body in [&b"ok"[..], b"created", b"accepted"] {
+      source = source.resize(resize.clone())?;
+      assert_eq!(source.to_buffer().await?, body);
+    }
+
+    source = source.resize(resize.clone())?;
+    assert_matches!(
+      source.to_buffer().await,
+      Err(TinifyError::ServerError { upstream, .. })
+        if upstream.error == "Internal Server Error"
+          && upstream.message == "upstream timed out"
+    );
+
+    source = source.resize(resize)?;
+    assert_matches!(
+      source.to_buffer().await,
+      Err(TinifyError::ServerError { upstream, .. }) if upstream.error == "Status"
+    );
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 7);
+    assert!(requests[2..]
+      .iter()
+      .all(|request| request.starts_with("POST /output/2xnsp7jn34e5 ")));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_from_url_with_auth() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let client = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .dry_run(true)
+      .get_async_client()?;
+
+    let (url, server) = mock::serve(vec![Reply::new("200 OK").body(png)]);
+    let source = client.from_url_with_auth(&url, "deploy", "s3cret").await?;
+    let request = server.join().unwrap().remove(0);
+    let authorization = request.lines().find_map(|line| {
+      let (name, value) = line.split_once(": ")?;
+      name.eq_ignore_ascii_case("authorization").then_some(value)
+    });
+    // `deploy:s3cret` in base64
+    assert_eq!(authorization, Some("Basic ZGVwbG95OnMzY3JldA=="));
+    assert_eq!(source.input_size(), Some(png.len()));
+
+    let (url, server) = mock::serve(vec![Reply::new("401 Unauthorized")]);
+    assert_matches!(
+      client.from_url_with_auth(&url, "deploy", "wrong").await,
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Source"
+    );
+    server.join().unwrap();
+
+    let (url, server) = mock::serve(vec![Reply::new("200 OK")
+      .header("Content-Type", "text/html")
+      .body(b"<html>Sign in</html>")]);
+    assert_matches!(
+      client.from_url_with_auth(&url, "deploy", "s3cret").await,
       Err(TinifyError::ClientError { upstream })
+        if upstream.message.contains("`text/html`")
+    );
+    server.join().unwrap();
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_store() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let stored = "https://s3-us-west-1.amazonaws.com/bucket/optimized.webp";
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK")
+        .header("Content-Type", "image/png")
+        .body(png),
+      Reply::new("200 OK").header("Location", stored),
+    ]);
+    let client = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?;
+    let store =
+      Store::s3("AKIA", "s3cret", "us-west-1", "bucket/optimized.webp");
+    let mut source = client.from_buffer(png).await?.convert(Convert {
+      r#type: vec![Type::Webp],
+    })?;
+
+    assert_eq!(source.store(store).await?.as_str(), stored);
+    assert_eq!(source.operations_summary(), "none");
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].starts_with("POST /shrink "), "{}", requests[0]);
+    assert!(requests[1].starts_with("GET /output/2xnsp7jn34e5 "));
+    assert!(requests[2].starts_with("POST /output/2xnsp7jn34e5 "));
+    assert!(requests[2].ends_with(concat!(
+      r#"{"convert":{"type":["image/webp"]},"store":{"service":"s3","#,
+      r#""aws_access_key_id":"AKIA","aws_secret_access_key":"s3cret","#,
+      r#""region":"us-west-1","path":"bucket/optimized.webp"}}"#,
+    )));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_responsive_set_from_cache() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_responsive_from_cache");
+    seed_cache(&dir, png, &Operations::default(), png)?;
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+      Reply::new("200 OK").body(b"small"),
+      Reply::new("200 OK").body(b"large"),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .set_cache_dir(&dir)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .preserve(Preserve {
+        metadata: vec![crate::preserve::Metadata::Copyright],
+      })?;
+    assert!(source.url().is_none());
+
+    let variants = source.responsive_set(&[200, 400], Type::Webp).await?;
+
+    assert_eq!(
+      variants,
+      vec![(200, b"small".to_vec()), (400, b"large".to_vec())]
+    );
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 4);
+    assert!(requests[0].starts_with("POST /shrink "));
+    assert!(requests[3].starts_with("POST /output/2xnsp7jn34e5 "));
+    assert!(requests[3].contains(r#""width":400"#));
+    assert!(requests[3].contains(r#""preserve""#));
+    assert_eq!(source.operations_summary(), "none");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_responsive_set_checks_variants() -> Result<(), TinifyError> {
+    let png = tiny_rgba_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .reject_upscale(true)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .preserve(Preserve {
+        metadata: vec![crate::preserve::Metadata::Copyright],
+      })?;
+
+    for (widths, r#type, error) in [
+      (&[200, 0][..], Type::Png, "Resize"),
+      (&[200, 2048][..], Type::Png, "Upscale"),
+      (&[200][..], Type::Jpeg, "Transparency"),
+    ] {
+      assert_matches!(
+        source.responsive_set(widths, r#type).await,
+        Err(TinifyError::ClientError { ref upstream }) if upstream.error == error
+      );
+    }
+    assert_eq!(source.operations_summary(), "preserve(copyright)");
+    assert_eq!(server.join().unwrap().len(), 2);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_durations_of_upload() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+    ]);
+    let source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+
+    assert!(source.upload_duration().is_some());
+    assert!(source.download_duration().is_some());
+    assert!(source.processing_duration().is_none());
+    assert_eq!(server.join().unwrap().len(), 2);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_debug_redacts_result() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK")
+        .header("Location", "/output/2xnsp7jn34e5")
+        .body(png),
+    ]);
+    let source = crate::async_bin::Tinify::new()
+      .set_key("s3cret")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+    let debug = format!("{:?}", source);
+
+    assert!(
+      debug.contains(&format!("buffer: Some({})", png.len())),
+      "{}",
+      debug
+    );
+    for secret in ["s3cret", "2xnsp7jn34e5"] {
+      assert!(!debug.contains(secret), "{}", debug);
     }
+    assert_eq!(server.join().unwrap().len(), 2);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_download_accepts_any_success() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("203 Non-Authoritative Information").body(png),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+
+    assert_eq!(source.to_buffer().await?, png);
+    assert_eq!(server.join().unwrap().len(), 2);
+
+    Ok(())
+  }
+
+  /// A path that panics once used, failing the task that writes to it.
+  struct PanickingPath;
+
+  impl AsRef<Path> for PanickingPath {
+    fn as_ref(&self) -> &Path {
+      panic!("the path is unavailable");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_to_file_keeps_buffer_on_panic() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+    server.join().unwrap();
+
+    assert_matches!(
+      source.to_file(PanickingPath).await,
+      Err(TinifyError::TokioError(_))
+    );
+    assert_eq!(source.to_buffer().await?, png);
+
+    Ok(())
+  }
+
+  /// A path that is only handed out after a while, keeping the task that writes to it busy.
+  struct SlowPath(PathBuf);
+
+  impl AsRef<Path> for SlowPath {
+    fn as_ref(&self) -> &Path {
+      thread::sleep(Duration::from_millis(200));
+      &self.0
+    }
+  }
+
+  #[tokio::test]
+  async fn test_to_file_keeps_buffer_on_cancel() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+    server.join().unwrap();
+    let path = env::temp_dir().join("tinify_cancelled_to_file/missing/out.png");
+
+    let cancelled = tokio::time::timeout(
+      Duration::from_millis(10),
+      source.to_file(SlowPath(path)),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    assert_eq!(source.to_buffer().await?, png);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_failed_operations_clear_pending() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png),
+      Reply::new("500 Internal Server Error")
+        .body(br#"{"error":"InternalServerError","message":"Oops!"}"#),
+    ]);
+    let mut source = crate::async_bin::Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .resize(Resize {
+        method: Method::Scale,
+        width: Some(400),
+        height: None,
+      })?;
+
+    assert_matches!(
+      source.to_buffer().await,
+      Err(TinifyError::ServerError { ref upstream, .. }) if upstream.message == "Oops!"
+    );
+    assert_eq!(source.operations_summary(), "none");
+    assert_eq!(server.join().unwrap().len(), 3);
+
+    Ok(())
   }
 }
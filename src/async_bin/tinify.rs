@@ -1,16 +1,35 @@
 use crate::async_bin::client::Client;
+use crate::cache::Cache;
+use crate::convert::Convert;
 use crate::error::TinifyError;
+use crate::error::Upstream;
+use crate::limit;
+use crate::location::endpoint_url;
+use crate::preserve::Preserve;
+use crate::resize::Resize;
+use crate::transform::Background;
+use crate::transform::Transform;
+use crate::Config;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// Use the API to create a new client.
 #[derive(Default)]
+#[must_use = "the builder does nothing until a client is created from it"]
 pub struct Tinify {
   pub key: String,
+  config: Config,
 }
 
 impl Tinify {
   /// Create a new Tinify Object.
   pub fn new() -> Self {
-    Self { key: String::new() }
+    Self {
+      key: String::new(),
+      config: Config::default(),
+    }
   }
 
   /// Set a Tinify Key.
@@ -22,6 +41,182 @@ impl Tinify {
     self
   }
 
+  /// Read the Tinify Key from a file, such as a mounted secret, ignoring the surrounding whitespace.
+  ///
+  /// Fails with an IO error when the file cannot be read, and with a `ClientError` whose error is `Key` when it is empty.
+  pub fn set_key_file<P>(mut self, path: P) -> Result<Self, TinifyError>
+  where
+    P: AsRef<Path>,
+  {
+    let path = path.as_ref();
+    let key = fs::read_to_string(path)?;
+    let key = key.trim();
+
+    if key.is_empty() {
+      let upstream = Upstream {
+        error: "Key".to_string(),
+        message: format!("The key file `{}` is empty.", path.display()),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+    self.key = key.to_string();
+
+    Ok(self)
+  }
+
+  /// Cache compressed images in a directory.
+  ///
+  /// Compressing the same bytes with the same operations again reads the result from the cache instead of calling the API. Images compressed from an url are never cached.
+  pub fn set_cache_dir<P>(mut self, dir: P) -> Self
+  where
+    P: Into<PathBuf>,
+  {
+    self.config.cache = Some(Cache::new(dir.into()));
+    self
+  }
+
+  /// Set the timeout of compressing from an url, which includes Tinify downloading the original.
+  ///
+  /// A slow origin then fails on its own budget, the other requests keep the default timeout of 300 seconds.
+  pub fn set_source_fetch_timeout(mut self, timeout: Duration) -> Self {
+    self.config.source_fetch_timeout = Some(timeout);
+    self
+  }
+
+  /// Reject animated PNG and WebP images before uploading them, instead of waiting for Tinify to fail on them.
+  pub fn reject_animated(mut self, reject: bool) -> Self {
+    self.config.reject_animated = reject;
+    self
+  }
+
+  /// Reject files whose extension isn't `png`, `jpg`, `jpeg`, `webp` or `avif` before reading them, and files whose content isn't one of these formats before uploading them.
+  ///
+  /// Extensions can lie, so this is off by default. It saves a read and an upload for every `.gif` or `.tiff` met while walking a directory.
+  pub fn reject_unsupported_extensions(mut self, reject: bool) -> Self {
+    self.config.reject_unsupported_extensions = reject;
+    self
+  }
+
+  /// Set how many bytes of an error response are read before giving up on it, 64 KB by default.
+  ///
+  /// A longer error body is not parsed, it ends up truncated in a `ClientError` or `ServerError` whose error is `Truncated`.
+  pub fn set_error_body_limit(mut self, limit: usize) -> Self {
+    self.config.error_body_limit = Some(limit);
+    self
+  }
+
+  /// Validate everything client side without calling the API or spending compressions.
+  ///
+  /// Choosing an image doesn't upload it, and saving it fails with `TinifyError::DryRun`, holding the [`RequestPlan`](crate::plan::RequestPlan) of what would have been sent. A validation error is returned as usual.
+  pub fn dry_run(mut self, dry_run: bool) -> Self {
+    self.config.dry_run = dry_run;
+    self
+  }
+
+  /// Reject a resize that would upscale the input before the request, off by default.
+  ///
+  /// Tinify only scales images down and silently returns an image of the original size instead. With this set such a resize fails with a `ClientError` whose error is `Upscale`: a `scale` to a width or height larger than the input, or a `fit` into a box larger than the input on both sides. The input is measured as displayed, after its EXIF orientation.
+  pub fn reject_upscale(mut self, reject: bool) -> Self {
+    self.config.reject_upscale = reject;
+    self
+  }
+
+  /// Create the missing parent directories of an output before writing it, off by default, for `to_file`, `save_both`, `compress_many` and `compress_archive`.
+  pub fn create_dirs(mut self, create: bool) -> Self {
+    self.config.create_dirs = create;
+    self
+  }
+
+  /// Let outputs replace an existing file, on by default. When off an existing file fails with an `AlreadyExists` IO error and is left untouched, so scripts never clobber an output by accident.
+  ///
+  /// This applies to every file written for you: `to_file`, the image and the sidecar of `save_both`, every file of `compress_many`, including the copies of a deduplicated batch, and the archive of `compress_archive`. Compressing in place replaces its input on purpose and ignores it.
+  pub fn overwrite(mut self, overwrite: bool) -> Self {
+    self.config.no_overwrite = !overwrite;
+    self
+  }
+
+  /// Write the original bytes with `to_file`, `compress_file_in_place` and `compress_in_memory` when the compressed image is not smaller, so the output is never larger than the input, off by default.
+  ///
+  /// This only applies to the plain compression of an uploaded image: once operations like a resize or a convert ran, their result is always written. Check `Source::kept_original` to tell which one was written.
+  pub fn keep_original_if_larger(mut self, keep: bool) -> Self {
+    self.config.keep_original_if_larger = keep;
+    self
+  }
+
+  /// Check that every downloaded result is a PNG, JPEG, WebP or AVIF image with a readable header, off by default.
+  ///
+  /// This guards pipelines against a body that slipped through with a success status but isn't an image, like an error page of a proxy. Such a result fails with a `ServerError` whose error is `Output`, before it is cached or saved. A result streamed with `into_byte_stream` is not checked.
+  pub fn verify_output(mut self, verify: bool) -> Self {
+    self.config.verify_output = verify;
+    self
+  }
+
+  /// Compress files with the same content only once in a `compress_many` batch, off by default.
+  ///
+  /// Asset bundles often contain copies of the same image. A file whose bytes match an earlier file of the batch gets a copy of its result instead of costing another compression.
+  pub fn dedup_batches(mut self, dedup: bool) -> Self {
+    self.config.dedup_batches = dedup;
+    self
+  }
+
+  /// Keep at most `idle` unused connections per host open for reuse, unlimited by default like reqwest.
+  ///
+  /// An `idle` of `0` disables the reuse.
+  pub fn pool_max_idle_per_host(mut self, idle: usize) -> Self {
+    self.config.pool_max_idle_per_host = Some(idle);
+    self
+  }
+
+  /// Limit the requests in flight to Tinify to `limit` for the whole process, across every client of every builder that sets it.
+  ///
+  /// Another limit replaces it for all of them, a `limit` of `0` is treated as `1`.
+  pub fn set_global_concurrency(mut self, limit: usize) -> Self {
+    self.config.concurrency = Some(limit::global(limit));
+    self
+  }
+
+  /// Convert every image compressed by the clients of this builder, unless a source sets its own `convert`.
+  ///
+  /// Default operations are set on every new source and replaced one by one: calling `convert` on a source overrides this default, while a default `resize` or `preserve` still applies. They run like any other operation, so a source without operations of its own still costs the extra compression.
+  pub fn default_convert(mut self, convert: Convert) -> Self {
+    self.config.default_operations.convert = Some(convert);
+    self
+  }
+
+  /// Resize every image compressed by the clients of this builder, unless a source sets its own `resize`.
+  pub fn default_resize(mut self, resize: Resize) -> Self {
+    self.config.default_operations.resize = Some(resize);
+    self
+  }
+
+  /// Transform every image compressed by the clients of this builder, unless a source sets its own `transform`.
+  pub fn default_transform(mut self, transform: Transform) -> Self {
+    self.config.default_operations.transform = Some(transform);
+    self
+  }
+
+  /// Fill the transparency with `background` whenever a source converts to JPEG only, unless it sets its own `transform`.
+  ///
+  /// Without it such a convert of a transparent input fails with a `ClientError` whose error is `Transparency`.
+  pub fn default_background(mut self, background: Background) -> Self {
+    self.config.default_background = Some(background);
+    self
+  }
+
+  /// Preserve metadata of every image compressed by the clients of this builder, unless a source sets its own `preserve`.
+  pub fn default_preserve(mut self, preserve: Preserve) -> Self {
+    self.config.default_operations.preserve = Some(preserve);
+    self
+  }
+
+  /// Send the requests to `endpoint` instead of `https://api.tinify.com`, like a proxy or a local mock server in tests.
+  ///
+  /// The key is sent to it too, so only point it at a server you trust.
+  pub fn set_endpoint(mut self, endpoint: &str) -> Result<Self, TinifyError> {
+    self.config.endpoint = Some(endpoint_url(endpoint)?);
+    Ok(self)
+  }
+
   /// Get a new Tinify Client.
   ///
   /// # Examples
@@ -40,7 +235,9 @@ impl Tinify {
   /// }
   /// ```
   pub fn get_async_client(&self) -> Result<Client, TinifyError> {
-    let client = Client::new(&self.key);
+    let client = Client::new(&self.key)
+      .with_config(self.config.clone())
+      .with_pool()?;
 
     Ok(client)
   }
@@ -50,8 +247,21 @@ impl Tinify {
 #[cfg(feature = "async")]
 mod tests {
   use super::*;
+  use crate::convert::Type;
+  use crate::fixture::seed_cache;
+  use crate::fixture::tiny_png;
+  use crate::fixture::tiny_rgba_png;
+  use crate::format::DetectedFormat;
+  use crate::mock;
+  use crate::mock::Reply;
+  use crate::preserve::Metadata;
+  use crate::resize::Method;
+  use crate::template::OutputTemplate;
+  use crate::Operations;
+  use assert_matches::assert_matches;
   use dotenv::dotenv;
   use std::env;
+  use std::io;
 
   #[test]
   fn test_get_async_client() -> Result<(), TinifyError> {
@@ -64,4 +274,507 @@ mod tests {
 
     Ok(())
   }
+
+  #[tokio::test]
+  async fn test_source_fetch_timeout() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let slow = Duration::from_millis(300);
+    let (url, server) = mock::serve(vec![
+      Reply::new("201 Created")
+        .header("Location", "/output/2xnsp7jn34e5")
+        .delay(slow),
+      Reply::new("201 Created").header("Location", "/output/2xnsp7jn34e5"),
+      Reply::new("200 OK").body(png).delay(slow),
+      Reply::new("200 OK").body(png).delay(slow),
+    ]);
+    let client = Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .set_source_fetch_timeout(Duration::from_millis(100))
+      .get_async_client()?;
+    let panda = "https://tinypng.com/images/panda-happy.png";
+
+    assert_matches!(client.from_url(panda).await, Err(TinifyError::Timeout(_)));
+    // The server answers the first request late, before it takes the next.
+    tokio::time::sleep(slow).await;
+    // Only the shrink of an url has the short timeout, not its download or the operations.
+    let mut source = client.from_url(panda).await?.resize(Resize {
+      method: Method::Scale,
+      width: Some(200),
+      height: None,
+    })?;
+    assert_eq!(source.to_buffer().await?, png);
+    assert_eq!(server.join().unwrap().len(), 4);
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_reject_animated() -> Result<(), TinifyError> {
+    let apng = b"\x89PNG\r\n\x1a\n\0\0\0\x08acTL\0\0\0\x02\0\0\0\0\0\0\0\0";
+    let request = Tinify::new()
+      .reject_animated(true)
+      .get_async_client()?
+      .from_buffer(apng)
+      .await
+      .unwrap_err();
+
+    assert_matches!(request, TinifyError::ClientError { ref upstream } if upstream.error == "Animated");
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_reject_unsupported_extensions() -> Result<(), TinifyError> {
+    let request = Tinify::new()
+      .reject_unsupported_extensions(true)
+      .get_async_client()?
+      .from_file("./animation.gif")
+      .await
+      .unwrap_err();
+
+    assert_matches!(request, TinifyError::ClientError { ref upstream } if upstream.error == "Extension");
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_dry_run() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let resize = Resize {
+      method: Method::Scale,
+      width: Some(400),
+      height: None,
+    };
+    let request = Tinify::new()
+      .dry_run(true)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .resize(resize)?
+      .to_buffer()
+      .await
+      .unwrap_err();
+
+    assert_matches!(request, TinifyError::DryRun(ref plan) if plan.dry_run);
+    if let TinifyError::DryRun(plan) = request {
+      assert_eq!(
+        plan.operations.as_deref(),
+        Some(r#"{"resize":{"method":"scale","width":400}}"#)
+      );
+    }
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_default_operations() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let client = Tinify::new()
+      .dry_run(true)
+      .default_convert(Convert {
+        r#type: vec![Type::Webp],
+      })
+      .default_preserve(Preserve {
+        metadata: vec![Metadata::Copyright],
+      })
+      .get_async_client()?;
+    let source = client.from_buffer(png).await?;
+
+    assert_eq!(
+      source.request_body()?,
+      r#"{"convert":{"type":["image/webp"]},"preserve":["copyright"]}"#
+    );
+
+    let source = client.from_buffer(png).await?.convert(Convert {
+      r#type: vec![Type::Png],
+    })?;
+
+    assert_eq!(
+      source.request_body()?,
+      r#"{"convert":{"type":["image/png"]},"preserve":["copyright"]}"#
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_set_key_file() -> Result<(), TinifyError> {
+    let path = env::temp_dir().join("tinify_async_key_file");
+    fs::write(&path, "  secret\n")?;
+    let tinify = Tinify::new().set_key_file(&path)?;
+
+    assert_eq!(tinify.key, "secret");
+
+    fs::write(&path, " \n")?;
+    let empty = Tinify::new().set_key_file(&path).map(|tinify| tinify.key);
+    fs::remove_file(&path)?;
+
+    assert_matches!(empty, Err(TinifyError::ClientError { ref upstream }) if upstream.error == "Key");
+    assert_matches!(
+      Tinify::new().set_key_file(path).map(|tinify| tinify.key),
+      Err(TinifyError::IOError(_))
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_keep_original_if_larger() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_keep_original");
+    let output = dir.join("output.png");
+    seed_cache(&dir, png, &Operations::default(), &[0; 64])?;
+    let mut source = Tinify::new()
+      .set_cache_dir(&dir)
+      .keep_original_if_larger(true)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+    source.to_file(output.clone()).await?;
+
+    assert!(source.kept_original());
+    assert_eq!(fs::read(&output)?, png);
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_overwrite_disabled() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_overwrite");
+    let output = dir.join("output.png");
+    seed_cache(&dir, png, &Operations::default(), png)?;
+    fs::write(&output, b"existing")?;
+    let mut source = Tinify::new()
+      .set_cache_dir(&dir)
+      .overwrite(false)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+
+    assert_matches!(
+      source.to_file(output.clone()).await,
+      Err(TinifyError::IOError(err)) if err.kind() == io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(fs::read(&output)?, b"existing");
+
+    fs::remove_file(&output)?;
+    source.to_file(output.clone()).await?;
+    assert_eq!(fs::read(&output)?, png);
+
+    let other = dir.join("other.png");
+    fs::write(dir.join("other.png.json"), b"existing")?;
+    assert_matches!(
+      source.save_both(other.clone()).await,
+      Err(TinifyError::IOError(err)) if err.kind() == io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(fs::read(dir.join("other.png.json"))?, b"existing");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_overwrite_disabled_in_batches() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_overwrite_batches");
+    seed_cache(
+      &dir.join("cache"),
+      png,
+      &Operations::default(),
+      b"compressed",
+    )?;
+    let inputs = [dir.join("a.png"), dir.join("b.png")];
+    for input in &inputs {
+      fs::write(input, png)?;
+    }
+    fs::write(dir.join("b.min.png"), b"existing")?;
+    let template = OutputTemplate::new("{dir}/{stem}.min.{ext}")?;
+    let results = Tinify::new()
+      .set_cache_dir(dir.join("cache"))
+      .dedup_batches(true)
+      .overwrite(false)
+      .get_async_client()?
+      .compress_many(&inputs, &template)
+      .await;
+
+    assert_eq!(fs::read(results[0].1.as_ref().unwrap())?, b"compressed");
+    assert_matches!(
+      &results[1].1,
+      Err(TinifyError::IOError(err)) if err.kind() == io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(fs::read(dir.join("b.min.png"))?, b"existing");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_input_and_output_format() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let webp = b"RIFF\x24\0\0\0WEBPVP8 ";
+    let dir = env::temp_dir().join("tinify_async_formats");
+    seed_cache(&dir, png, &Operations::default(), webp)?;
+    let source = Tinify::new()
+      .set_cache_dir(&dir)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?;
+
+    assert_eq!(source.input_format(), Some(DetectedFormat::Png));
+    assert_eq!(source.output_format(), Some(DetectedFormat::Webp));
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_request_plan_without_key() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let plan = Tinify::new()
+      .set_key("secret-key")
+      .dry_run(true)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .resize(Resize::fit_within(2, 2))?
+      .request_plan()?;
+    let json = serde_json::to_string(&plan)?;
+
+    assert!(plan.dry_run);
+    assert!(!plan.uploaded);
+    assert_eq!(
+      plan.operations.as_deref(),
+      Some(r#"{"resize":{"method":"fit","width":2,"height":2}}"#)
+    );
+    assert!(!json.contains("secret-key"));
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_reset_operations() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let dir = env::temp_dir().join("tinify_async_reset_operations");
+    let mut operations = Operations::default();
+    seed_cache(&dir, png, &operations, png)?;
+    for (size, variant) in [(2, b"variant 2"), (1, b"variant 1")] {
+      operations.resize = Some(Resize::fit_within(size, size));
+      seed_cache(&dir, png, &operations, variant)?;
+    }
+    let client = Tinify::new().set_cache_dir(&dir).get_async_client()?;
+
+    let mut source = client
+      .from_buffer(png)
+      .await?
+      .resize(Resize::fit_within(2, 2))?;
+    assert_eq!(source.to_buffer().await?, b"variant 2");
+
+    let mut source = source.resize(Resize::fit_within(3, 3))?;
+    source.reset_operations();
+    assert_eq!(source.operations_summary(), "none");
+
+    let mut source = source.resize(Resize::fit_within(1, 1))?;
+    assert_eq!(source.to_buffer().await?, b"variant 1");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_default_background() -> Result<(), TinifyError> {
+    let rgba = tiny_rgba_png();
+    let jpeg = || Convert {
+      r#type: vec![Type::Jpeg],
+    };
+    let client = Tinify::new()
+      .dry_run(true)
+      .default_background(Background::White)
+      .get_async_client()?;
+
+    let source = client.from_buffer(rgba).await?.convert(jpeg())?;
+    assert_eq!(
+      source.request_body()?,
+      r#"{"convert":{"type":["image/jpeg"]},"transform":{"background":"white"}}"#
+    );
+
+    let source = client.from_buffer(rgba).await?.convert(jpeg())?.transform(
+      Transform {
+        background: Background::Black,
+      },
+    )?;
+    assert_eq!(
+      source.request_body()?,
+      r#"{"convert":{"type":["image/jpeg"]},"transform":{"background":"black"}}"#
+    );
+
+    let source = client.from_buffer(rgba).await?.convert(Convert {
+      r#type: vec![Type::Jpeg, Type::Webp],
+    })?;
+    assert_eq!(
+      source.request_body()?,
+      r#"{"convert":{"type":["image/jpeg","image/webp"]}}"#
+    );
+
+    let source = Tinify::new()
+      .dry_run(true)
+      .get_async_client()?
+      .from_buffer(rgba)
+      .await?
+      .convert(jpeg())?;
+    assert_matches!(
+      source.request_body(),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Transparency"
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_dedup_batches() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let rgba = tiny_rgba_png();
+    let dir = env::temp_dir().join("tinify_async_dedup");
+    fs::create_dir_all(&dir)?;
+    let inputs = [dir.join("a.png"), dir.join("b.png"), dir.join("c.png")];
+    for (input, bytes) in inputs.iter().zip([png, png, rgba]) {
+      fs::write(input, bytes)?;
+    }
+    // Two uploads only, a third one would find the server gone.
+    let (url, server) = crate::mock::serve(vec![
+      Reply::new("201 Created").header("Location", "/output/first"),
+      Reply::new("200 OK").body(b"first"),
+      Reply::new("201 Created").header("Location", "/output/second"),
+      Reply::new("200 OK").body(b"second"),
+    ]);
+    let template = OutputTemplate::new("{dir}/optimized/{stem}.{ext}")?;
+    let results = Tinify::new()
+      .set_key("key")
+      .set_endpoint(&url)?
+      .create_dirs(true)
+      .dedup_batches(true)
+      .get_async_client()?
+      .compress_many(&inputs, &template)
+      .await;
+
+    let outputs = results
+      .into_iter()
+      .map(|(_, result)| Ok(fs::read(result?)?))
+      .collect::<Result<Vec<_>, TinifyError>>()?;
+    assert_eq!(outputs, [&b"first"[..], b"first", b"second"]);
+    let requests = server.join().unwrap();
+    let uploads = requests
+      .iter()
+      .filter(|request| request.starts_with("POST /shrink "))
+      .count();
+    assert_eq!(uploads, 2);
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_reject_upscale() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let scale = Resize {
+      method: Method::Scale,
+      width: Some(2000),
+      height: None,
+    };
+
+    let source = Tinify::new()
+      .dry_run(true)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .resize(scale.clone())?;
+    assert!(source.request_body().is_ok());
+
+    let source = Tinify::new()
+      .dry_run(true)
+      .reject_upscale(true)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .resize(scale)?;
+    assert_matches!(
+      source.request_body(),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Upscale"
+    );
+
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn test_convert_and_report_from_cache() -> Result<(), TinifyError> {
+    let png = tiny_png();
+    let webp = b"RIFF\x04\0\0\0WEBPVP8 ";
+    let dir = env::temp_dir().join("tinify_async_convert_from_cache");
+    let convert = Convert {
+      r#type: vec![Type::Png, Type::Webp],
+    };
+    let operations = Operations {
+      convert: Some(convert.clone()),
+      ..Operations::default()
+    };
+    seed_cache(&dir, png, &Operations::default(), png)?;
+    seed_cache(&dir, png, &operations, webp)?;
+
+    let (source, r#type) = Tinify::new()
+      .set_cache_dir(&dir)
+      .get_async_client()?
+      .from_buffer(png)
+      .await?
+      .convert_and_report(convert)
+      .await?;
+
+    assert_matches!(r#type, Type::Webp);
+    assert_eq!(source.content_type(), Some("image/webp"));
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_global_concurrency() -> Result<(), TinifyError> {
+    let server = crate::mock::KeepAlive::serve(Duration::from_millis(50));
+    let first = Tinify::new()
+      .set_key("key")
+      .set_endpoint(&server.url)?
+      .set_global_concurrency(4)
+      .get_async_client()?;
+    let second = Tinify::new()
+      .set_key("key")
+      .set_endpoint(&server.url)?
+      .set_global_concurrency(2)
+      .get_async_client()?;
+    let requests: Vec<_> = [&first, &second, &first, &second]
+      .repeat(2)
+      .into_iter()
+      .map(|client| {
+        let client = client.clone();
+        // The empty answer is no upload, only the requests in flight count.
+        tokio::spawn(async move {
+          client.from_url("https://example.com/panda.png").await
+        })
+      })
+      .collect();
+
+    for request in requests {
+      let _ = request.await.unwrap();
+    }
+
+    assert_eq!(server.most_in_flight(), 2);
+
+    Ok(())
+  }
 }
@@ -0,0 +1,120 @@
+use crate::error::TinifyError;
+use crate::write_atomic;
+use crate::Operations;
+use sha2::Digest as _;
+use sha2::Sha256;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The SHA-256 of an input, two different inputs never share a cache entry.
+pub(crate) type Digest = [u8; 32];
+
+pub(crate) fn digest(bytes: &[u8]) -> Digest {
+  Sha256::digest(bytes).into()
+}
+
+/// Lowercase hex digits of a digest.
+pub(crate) fn hex(digest: &Digest) -> String {
+  digest
+    .iter()
+    .fold(String::with_capacity(64), |mut hex, byte| {
+      let _ = write!(hex, "{:02x}", byte);
+      hex
+    })
+}
+
+/// An on-disk cache of compressed images.
+///
+/// Entries are keyed by the SHA-256 of the input bytes and of the serialized operations, so changing any operation misses the cache instead of returning a stale image. Entries are written atomically, a crash never leaves a truncated image to be served later.
+#[derive(Clone, Debug)]
+pub(crate) struct Cache {
+  dir: PathBuf,
+}
+
+impl Cache {
+  pub(crate) fn new(dir: PathBuf) -> Self {
+    Self { dir }
+  }
+
+  pub(crate) fn key(
+    &self,
+    input: Digest,
+    operations: &Operations,
+  ) -> Result<String, TinifyError> {
+    let operations = serde_json::to_vec(operations)?;
+
+    Ok(format!("{}-{}", hex(&input), hex(&digest(&operations))))
+  }
+
+  pub(crate) fn get(&self, key: &str) -> Result<Option<Vec<u8>>, TinifyError> {
+    match fs::read(self.dir.join(key)) {
+      Ok(bytes) => Ok(Some(bytes)),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  pub(crate) fn put(&self, key: &str, bytes: &[u8]) -> Result<(), TinifyError> {
+    fs::create_dir_all(&self.dir)?;
+    write_atomic(&self.dir.join(key), bytes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::resize::Method;
+  use crate::resize::Resize;
+  use std::env;
+  use std::process;
+
+  #[test]
+  fn test_digest() {
+    assert_eq!(
+      hex(&digest(b"abc")),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_ne!(digest(b"ab"), digest(b"ba"));
+  }
+
+  #[test]
+  fn test_key_changes_with_operations() -> Result<(), TinifyError> {
+    let cache = Cache::new(PathBuf::new());
+    let mut operations = Operations::default();
+    let plain = cache.key(digest(b"image"), &operations)?;
+
+    operations.resize = Some(Resize {
+      method: Method::Fit,
+      width: Some(400),
+      height: Some(200),
+    });
+
+    assert_ne!(cache.key(digest(b"image"), &operations)?, plain);
+    assert_ne!(cache.key(digest(b"other"), &Operations::default())?, plain);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_get_and_put() -> Result<(), TinifyError> {
+    let dir = env::temp_dir().join(format!("tinify-cache-{}", process::id()));
+    let cache = Cache::new(dir.clone());
+
+    assert_eq!(cache.get("missing")?, None);
+
+    cache.put("entry", b"compressed")?;
+
+    assert_eq!(cache.get("entry")?, Some(b"compressed".to_vec()));
+
+    cache.put("entry", b"recompressed")?;
+
+    assert_eq!(cache.get("entry")?, Some(b"recompressed".to_vec()));
+    assert_eq!(fs::read_dir(&dir)?.count(), 1);
+
+    fs::remove_dir_all(dir)?;
+
+    Ok(())
+  }
+}
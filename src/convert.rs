@@ -1,8 +1,12 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
 use serde::Deserialize;
 use serde::Serialize;
 
 /// The type `enum` defines the type of image to which it will be converted.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+///
+/// Each type serializes as its [`mime_type`](Type::mime_type), keep the serde renames in sync with it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum Type {
   #[serde(rename = "image/png")]
   Png,
@@ -17,13 +21,185 @@ pub enum Type {
   WildCard,
 }
 
+impl Type {
+  /// Every type, in declaration order.
+  pub(crate) const ALL: [Type; 4] =
+    [Type::Png, Type::Jpeg, Type::Webp, Type::WildCard];
+
+  /// Get the MIME type sent to the API for this type, like `image/webp`, or `*/*` for the wildcard.
+  pub fn mime_type(&self) -> &'static str {
+    match self {
+      Type::Png => "image/png",
+      Type::Jpeg => "image/jpeg",
+      Type::Webp => "image/webp",
+      Type::WildCard => "*/*",
+    }
+  }
+}
+
+impl TryFrom<&str> for Type {
+  type Error = TinifyError;
+
+  /// Parse a MIME type, such as the `Content-Type` of a result, ignoring its parameters.
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    let mime = value.split(';').next().unwrap_or_default().trim();
+
+    match Type::ALL
+      .into_iter()
+      .find(|r#type| r#type.mime_type().eq_ignore_ascii_case(mime))
+    {
+      Some(r#type) => Ok(r#type),
+      None => {
+        let upstream = Upstream {
+          error: "Type".to_string(),
+          message: format!("Unsupported image type `{}`.", value),
+        };
+        Err(TinifyError::ClientError { upstream })
+      }
+    }
+  }
+}
+
 /// # Converting images
 ///
 /// You can use the API to convert your images to your desired image type. Tinify currently supports converting between `WebP`, J`PEG`, and `PNG`. When you provide more than one image `type` in your convert request, the smallest version will be returned to you.
 ///
 /// Image converting will count as one additional compression.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+///
+/// Converting to the type the image already has is allowed and re-encodes it,
+/// which still counts as a compression.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Convert {
   /// A vector of `types`
   pub r#type: Vec<Type>,
 }
+
+/// Convert to the smallest of all types, `Type::WildCard`.
+impl Default for Convert {
+  fn default() -> Self {
+    Self {
+      r#type: vec![Type::WildCard],
+    }
+  }
+}
+
+impl Convert {
+  /// Convert to the smallest of the types an `Accept` header allows, like `image/webp,image/*;q=0.8`, narrowing `Type::WildCard` to what the clients asking for the image support.
+  ///
+  /// This is the same as a convert listing the accepted types, ordered by their `q` weight, the server still returns the smallest of them. `image/*` and `*/*` accept every concrete type, types refused with `q=0` are left out even when a wildcard accepts them, and a header without any supported type fails with a `ClientError` whose error is `Type`.
+  pub fn from_accept(accept: &str) -> Result<Self, TinifyError> {
+    let mut ranges: Vec<(&str, f32)> = accept
+      .split(',')
+      .filter_map(|range| {
+        let mut params = range.split(';');
+        let mime = params.next()?.trim();
+        let q = params
+          .filter_map(|param| param.trim().strip_prefix("q="))
+          .find_map(|q| q.trim().parse::<f32>().ok())
+          .unwrap_or(1.0);
+        (!mime.is_empty()).then_some((mime, q))
+      })
+      .collect();
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let concrete = |mime: &str| {
+      if mime == "*/*" || mime.eq_ignore_ascii_case("image/*") {
+        vec![Type::Png, Type::Jpeg, Type::Webp]
+      } else {
+        Type::try_from(mime).into_iter().collect()
+      }
+    };
+    let refused: Vec<Type> = ranges
+      .iter()
+      .filter(|(mime, q)| *q <= 0.0 && !mime.contains('*'))
+      .flat_map(|(mime, _)| concrete(mime))
+      .collect();
+
+    let mut types = Vec::new();
+    for (mime, q) in ranges {
+      if q <= 0.0 {
+        continue;
+      }
+      for r#type in concrete(mime) {
+        if !types.contains(&r#type) && !refused.contains(&r#type) {
+          types.push(r#type);
+        }
+      }
+    }
+
+    if types.is_empty() {
+      let upstream = Upstream {
+        error: "Type".to_string(),
+        message: format!("No supported image type in `{}`.", accept),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+
+    Ok(Self { r#type: types })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+
+  #[test]
+  fn test_type_from_content_type() {
+    assert_matches!(Type::try_from("image/png"), Ok(Type::Png));
+    assert_matches!(Type::try_from("image/jpeg"), Ok(Type::Jpeg));
+    assert_matches!(Type::try_from("IMAGE/WEBP"), Ok(Type::Webp));
+    assert_matches!(
+      Type::try_from("image/webp; charset=binary"),
+      Ok(Type::Webp)
+    );
+    assert_matches!(Type::try_from("*/*"), Ok(Type::WildCard));
+    assert_matches!(
+      Type::try_from("text/html"),
+      Err(TinifyError::ClientError { .. })
+    );
+  }
+
+  #[test]
+  fn test_mime_type_round_trip() -> Result<(), TinifyError> {
+    // Fails to compile when a type is added, add it to `Type::ALL` as well.
+    match Type::Png {
+      Type::Png | Type::Jpeg | Type::Webp | Type::WildCard => {}
+    }
+
+    for r#type in Type::ALL {
+      let mime = r#type.mime_type();
+
+      assert_eq!(Type::try_from(mime)?, r#type);
+      assert_eq!(serde_json::to_string(&r#type)?, format!("\"{}\"", mime));
+      assert_eq!(
+        serde_json::from_str::<Type>(&format!("\"{}\"", mime))?,
+        r#type
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_from_accept() -> Result<(), TinifyError> {
+    assert_eq!(
+      Convert::from_accept("image/jpeg;q=0.5, image/webp")?.r#type,
+      [Type::Webp, Type::Jpeg]
+    );
+    assert_eq!(
+      Convert::from_accept("image/avif,image/webp,image/*;q=0.8")?.r#type,
+      [Type::Webp, Type::Png, Type::Jpeg]
+    );
+    assert_eq!(
+      Convert::from_accept("*/*, image/png;q=0")?.r#type,
+      [Type::Jpeg, Type::Webp]
+    );
+    assert_matches!(
+      Convert::from_accept("image/avif, text/html"),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Type"
+    );
+
+    Ok(())
+  }
+}
@@ -1,8 +1,12 @@
+use crate::plan::RequestPlan;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use serde::Serialize;
 use std::error;
 use std::fmt;
 use std::io;
+use std::num::ParseIntError;
+use std::time::Duration;
 #[cfg(feature = "async")]
 use tokio::task;
 
@@ -14,18 +18,39 @@ pub struct Upstream {
 }
 
 /// The `TinifyError` enum indicates whether a client or server error occurs.
+///
+/// New kinds of errors may be added in minor releases, so a `match` on it needs a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum TinifyError {
   ClientError {
     upstream: Upstream,
   },
+  /// The API failed or gave an answer the crate can't use. `status` is that of the response, `None` when only its content was rejected.
   ServerError {
     upstream: Upstream,
+    status: Option<StatusCode>,
   },
+  /// The API answered `429 Too Many Requests`, `retry_after` is how long it asked to wait, from its `Retry-After` header.
+  TooManyRequests {
+    upstream: Upstream,
+    retry_after: Option<Duration>,
+  },
+  /// A request took longer than its timeout, worth retrying.
+  Timeout(reqwest::Error),
+  /// A request couldn't connect to its server, the API or the origin of `from_url_with_auth`, or resolve its name. The network may be down, or the server refused the connection or its TLS handshake failed, worth retrying.
+  Offline(reqwest::Error),
+  /// A dry run stopped before anything was sent, with the plan of what saving would have sent.
+  DryRun(Box<RequestPlan>),
+  /// The task running a request was cancelled before it finished, retrying it is up to whoever cancelled it.
+  #[cfg(feature = "async")]
+  Cancelled,
   ReqwestError(reqwest::Error),
   ReqwestConvertError(reqwest::header::ToStrError),
   UrlParseError(url::ParseError),
   JsonParseError(serde_json::Error),
+  /// A numeric header, like `Compression-Count` or `Image-Width`, is not a number, only returned by the strict accessors.
+  ParseIntError(ParseIntError),
   IOError(io::Error),
   #[cfg(feature = "async")]
   TokioError(task::JoinError),
@@ -36,10 +61,17 @@ impl error::Error for TinifyError {
     match *self {
       TinifyError::ClientError { .. } => None,
       TinifyError::ServerError { .. } => None,
+      TinifyError::TooManyRequests { .. } => None,
+      TinifyError::Timeout(ref source) => Some(source),
+      TinifyError::Offline(ref source) => Some(source),
+      TinifyError::DryRun(_) => None,
+      #[cfg(feature = "async")]
+      TinifyError::Cancelled => None,
       TinifyError::ReqwestError(ref source) => Some(source),
       TinifyError::ReqwestConvertError(ref source) => Some(source),
       TinifyError::UrlParseError(ref source) => Some(source),
       TinifyError::JsonParseError(ref source) => Some(source),
+      TinifyError::ParseIntError(ref source) => Some(source),
       TinifyError::IOError(ref source) => Some(source),
       #[cfg(feature = "async")]
       TinifyError::TokioError(ref source) => Some(source),
@@ -53,13 +85,26 @@ impl fmt::Display for TinifyError {
       TinifyError::ClientError { ref upstream } => {
         write!(f, "Tinify Client Error: {}", upstream.message)
       }
-      TinifyError::ServerError { ref upstream } => {
+      TinifyError::ServerError { ref upstream, .. } => {
         write!(f, "Tinify Server Error: {}", upstream.message)
       }
+      TinifyError::TooManyRequests { ref upstream, .. } => {
+        write!(f, "Tinify Too Many Requests: {}", upstream.message)
+      }
+      TinifyError::Timeout(ref err) => err.fmt(f),
+      TinifyError::Offline(ref err) => {
+        write!(f, "Could not connect, the network may be down: {}", err)
+      }
+      TinifyError::DryRun(_) => f.write_str("Tinify dry run, nothing was sent"),
+      #[cfg(feature = "async")]
+      TinifyError::Cancelled => f.write_str("Tinify request cancelled"),
       TinifyError::ReqwestError(ref err) => err.fmt(f),
       TinifyError::ReqwestConvertError(ref err) => err.fmt(f),
       TinifyError::UrlParseError(ref err) => err.fmt(f),
       TinifyError::JsonParseError(ref err) => err.fmt(f),
+      TinifyError::ParseIntError(ref err) => {
+        write!(f, "Invalid numeric header: {}", err)
+      }
       TinifyError::IOError(ref err) => err.fmt(f),
       #[cfg(feature = "async")]
       TinifyError::TokioError(ref err) => err.fmt(f),
@@ -67,6 +112,31 @@ impl fmt::Display for TinifyError {
   }
 }
 
+impl TinifyError {
+  /// How long to wait before retrying a request that was rate limited, `None` for other errors and when the API didn't say.
+  pub fn retry_after(&self) -> Option<Duration> {
+    match *self {
+      TinifyError::TooManyRequests { retry_after, .. } => retry_after,
+      _ => None,
+    }
+  }
+
+  /// Whether the same request may succeed when sent again: a rate limit, a timeout, a `5xx` server error or a failed connection, which is `Offline`.
+  ///
+  /// A `ServerError` for a redirect, an unexpected success or a response rejected by the crate is not retryable, and neither is a `Cancelled` request or any other client error.
+  pub fn is_retryable(&self) -> bool {
+    match *self {
+      TinifyError::TooManyRequests { .. }
+      | TinifyError::Timeout(_)
+      | TinifyError::Offline(_) => true,
+      TinifyError::ServerError { status, .. } => {
+        status.is_some_and(|status| status.is_server_error())
+      }
+      _ => false,
+    }
+  }
+}
+
 impl From<io::Error> for TinifyError {
   fn from(err: io::Error) -> Self {
     TinifyError::IOError(err)
@@ -75,7 +145,13 @@ impl From<io::Error> for TinifyError {
 
 impl From<reqwest::Error> for TinifyError {
   fn from(err: reqwest::Error) -> Self {
-    TinifyError::ReqwestError(err)
+    if err.is_timeout() {
+      TinifyError::Timeout(err)
+    } else if err.is_connect() {
+      TinifyError::Offline(err)
+    } else {
+      TinifyError::ReqwestError(err)
+    }
   }
 }
 
@@ -97,9 +173,125 @@ impl From<serde_json::Error> for TinifyError {
   }
 }
 
+impl From<ParseIntError> for TinifyError {
+  fn from(err: ParseIntError) -> Self {
+    TinifyError::ParseIntError(err)
+  }
+}
+
 #[cfg(feature = "async")]
 impl From<tokio::task::JoinError> for TinifyError {
   fn from(err: tokio::task::JoinError) -> Self {
-    TinifyError::TokioError(err)
+    if err.is_cancelled() {
+      TinifyError::Cancelled
+    } else {
+      TinifyError::TokioError(err)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+  use std::net::TcpListener;
+  use std::thread;
+
+  /// Accept one connection and never answer it, like an endpoint too slow for any timeout.
+  fn slow_endpoint() -> Result<String, TinifyError> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    thread::spawn(move || {
+      let _connection = listener.accept();
+      thread::sleep(Duration::from_secs(5));
+    });
+
+    Ok(url)
+  }
+
+  #[test]
+  fn test_timeout() -> Result<(), TinifyError> {
+    let url = slow_endpoint()?;
+    let request = reqwest::blocking::Client::new()
+      .get(url)
+      .timeout(Duration::from_millis(50))
+      .send()
+      .map_err(TinifyError::from);
+
+    assert_matches!(request, Err(TinifyError::Timeout(_)));
+    assert!(request.unwrap_err().is_retryable());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_offline() -> Result<(), TinifyError> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    drop(listener);
+    let request = reqwest::blocking::Client::new()
+      .get(url)
+      .send()
+      .map_err(TinifyError::from);
+
+    assert_matches!(request, Err(TinifyError::Offline(_)));
+    let err = request.unwrap_err();
+    assert!(err.is_retryable());
+    assert!(err.to_string().starts_with("Could not connect"));
+    assert!(error::Error::source(&err).is_some());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_is_retryable() {
+    let upstream = || Upstream {
+      error: "Error".to_string(),
+      message: "message".to_string(),
+    };
+
+    assert!(TinifyError::TooManyRequests {
+      upstream: upstream(),
+      retry_after: None,
+    }
+    .is_retryable());
+    assert!(TinifyError::ServerError {
+      upstream: upstream(),
+      status: Some(StatusCode::SERVICE_UNAVAILABLE),
+    }
+    .is_retryable());
+    let output = crate::format::check_output(b"<html>").unwrap_err();
+    assert_matches!(output, TinifyError::ServerError { ref upstream, .. } if upstream.error == "Output");
+    assert!(!output.is_retryable());
+    for status in [StatusCode::OK, StatusCode::FOUND] {
+      let unexpected = crate::classify_status(
+        status,
+        &[StatusCode::CREATED],
+        None,
+        b"",
+        crate::DEFAULT_ERROR_BODY_LIMIT,
+      )
+      .unwrap_err();
+      assert_matches!(unexpected, TinifyError::ServerError { .. });
+      assert!(!unexpected.is_retryable());
+    }
+    assert!(!TinifyError::ClientError {
+      upstream: upstream()
+    }
+    .is_retryable());
+    assert!(
+      !TinifyError::IOError(io::ErrorKind::NotFound.into()).is_retryable()
+    );
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test]
+  async fn test_cancelled() {
+    let task = tokio::spawn(std::future::pending::<()>());
+    task.abort();
+    let cancelled = TinifyError::from(task.await.unwrap_err());
+
+    assert_matches!(cancelled, TinifyError::Cancelled);
+    assert!(!cancelled.is_retryable());
   }
 }
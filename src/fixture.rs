@@ -0,0 +1,29 @@
+//! Inputs shared by the tests, to compress images without calling the API.
+
+use crate::cache;
+use crate::cache::Cache;
+use crate::error::TinifyError;
+use crate::Operations;
+use std::path::Path;
+
+/// The header of a 1024 by 1024 RGB PNG, enough for the format detection and the client side validation.
+pub(crate) fn tiny_png() -> &'static [u8; 26] {
+  b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x04\0\0\0\x04\0\x08\x02"
+}
+
+/// The header of a 1024 by 1024 PNG with an alpha channel, like [`tiny_png`] otherwise.
+pub(crate) fn tiny_rgba_png() -> &'static [u8; 26] {
+  b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x04\0\0\0\x04\0\x08\x06"
+}
+
+/// Store `result` in the cache in `dir` as the result of compressing `input` with `operations`, so compressing it again is a cache hit.
+pub(crate) fn seed_cache(
+  dir: &Path,
+  input: &[u8],
+  operations: &Operations,
+  result: &[u8],
+) -> Result<(), TinifyError> {
+  let cache = Cache::new(dir.to_path_buf());
+
+  cache.put(&cache.key(cache::digest(input), operations)?, result)
+}
@@ -0,0 +1,776 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use std::path::Path;
+
+/// The format of an image, detected from its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DetectedFormat {
+  Png,
+  Jpeg,
+  Webp,
+  Gif,
+  Avif,
+}
+
+impl DetectedFormat {
+  /// Get the MIME type of the format, like `image/png`.
+  pub fn mime_type(self) -> &'static str {
+    match self {
+      DetectedFormat::Png => "image/png",
+      DetectedFormat::Jpeg => "image/jpeg",
+      DetectedFormat::Webp => "image/webp",
+      DetectedFormat::Gif => "image/gif",
+      DetectedFormat::Avif => "image/avif",
+    }
+  }
+
+  /// Find the format of a MIME type like the `Content-Type` of a result, ignoring its parameters.
+  pub(crate) fn from_mime_type(mime: &str) -> Option<Self> {
+    let mime = mime.split(';').next().unwrap_or_default().trim();
+
+    [
+      DetectedFormat::Png,
+      DetectedFormat::Jpeg,
+      DetectedFormat::Webp,
+      DetectedFormat::Gif,
+      DetectedFormat::Avif,
+    ]
+    .into_iter()
+    .find(|format| format.mime_type().eq_ignore_ascii_case(mime))
+  }
+}
+
+/// Detect the format of an image from its magic bytes, without decoding it.
+///
+/// Returns `None` when the bytes don't start like any known format.
+pub fn detect_format(bytes: &[u8]) -> Option<DetectedFormat> {
+  if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+    Some(DetectedFormat::Png)
+  } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+    Some(DetectedFormat::Jpeg)
+  } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+    Some(DetectedFormat::Gif)
+  } else if bytes.len() >= 12
+    && &bytes[..4] == b"RIFF"
+    && &bytes[8..12] == b"WEBP"
+  {
+    Some(DetectedFormat::Webp)
+  } else if is_avif(bytes) {
+    Some(DetectedFormat::Avif)
+  } else {
+    None
+  }
+}
+
+/// An AVIF file starts with an `ftyp` box listing `avif` or `avis` as its major or a compatible brand.
+fn is_avif(bytes: &[u8]) -> bool {
+  if bytes.len() < 16 || &bytes[4..8] != b"ftyp" {
+    return false;
+  }
+
+  let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+  let end = (size as usize).clamp(16, bytes.len());
+  let major = &bytes[8..12];
+  let mut compatible = bytes[16..end].chunks_exact(4);
+
+  [major]
+    .into_iter()
+    .chain(&mut compatible)
+    .any(|brand| brand == b"avif" || brand == b"avis")
+}
+
+/// Check whether an image is an animated PNG (APNG) or an animated WebP.
+///
+/// An APNG has an `acTL` chunk before its first `IDAT`, an animated WebP sets the animation flag of its `VP8X` chunk.
+pub fn is_animated(bytes: &[u8]) -> bool {
+  match detect_format(bytes) {
+    Some(DetectedFormat::Png) => is_animated_png(bytes),
+    Some(DetectedFormat::Webp) => is_animated_webp(bytes),
+    _ => false,
+  }
+}
+
+fn is_animated_png(bytes: &[u8]) -> bool {
+  let mut offset = 8;
+
+  while let Some(header) = bytes.get(offset..offset + 8) {
+    let length =
+      u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+
+    match &header[4..8] {
+      b"acTL" => return true,
+      b"IDAT" | b"IEND" => return false,
+      _ => offset += 12 + length as usize,
+    }
+  }
+
+  false
+}
+
+fn is_animated_webp(bytes: &[u8]) -> bool {
+  bytes.len() >= 21 && &bytes[12..16] == b"VP8X" && bytes[20] & 0x02 != 0
+}
+
+/// Read the `(width, height)` of a PNG, JPEG, WebP or GIF image from its header, without decoding it.
+///
+/// This is the size of the pixels as stored, before any EXIF orientation. Returns `None` for other formats or a truncated header.
+pub fn image_size(bytes: &[u8]) -> Option<(u32, u32)> {
+  let le16 = |i: usize| {
+    Some(u16::from_le_bytes([*bytes.get(i)?, *bytes.get(i + 1)?]) as u32)
+  };
+  let le24 = |i: usize| {
+    let b = bytes.get(i..i + 3)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+  };
+
+  match detect_format(bytes)? {
+    DetectedFormat::Png => {
+      let b = bytes.get(16..24)?;
+      Some((
+        u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+        u32::from_be_bytes([b[4], b[5], b[6], b[7]]),
+      ))
+    }
+    DetectedFormat::Gif => Some((le16(6)?, le16(8)?)),
+    DetectedFormat::Jpeg => jpeg_size(bytes),
+    DetectedFormat::Webp => match bytes.get(12..16)? {
+      b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+      b"VP8L" => {
+        let b = bytes.get(21..25)?;
+        let width = (b[0] as u32 | (b[1] as u32 & 0x3f) << 8) + 1;
+        let height =
+          (b[1] as u32 >> 6 | (b[2] as u32) << 2 | (b[3] as u32 & 0x0f) << 10)
+            + 1;
+        Some((width, height))
+      }
+      b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+      _ => None,
+    },
+    DetectedFormat::Avif => None,
+  }
+}
+
+/// The size of a JPEG is stored in its start of frame segment, `SOF0` to `SOF15` except `DHT`, `JPG` and `DAC`.
+fn jpeg_size(bytes: &[u8]) -> Option<(u32, u32)> {
+  let mut offset = 2;
+
+  while let Some(header) = bytes.get(offset..offset + 4) {
+    if header[0] != 0xff {
+      return None;
+    }
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+
+    if matches!(header[1], 0xc0..=0xcf)
+      && !matches!(header[1], 0xc4 | 0xc8 | 0xcc)
+    {
+      let frame = bytes.get(offset + 5..offset + 9)?;
+      let height = u16::from_be_bytes([frame[0], frame[1]]) as u32;
+      let width = u16::from_be_bytes([frame[2], frame[3]]) as u32;
+      return Some((width, height));
+    }
+    offset += 2 + length;
+  }
+
+  None
+}
+
+/// The sum of the example luminance quantization table of the JPEG standard, which encoders scale to reach a quality.
+const JPEG_LUMINANCE_SUM: u32 = 3688;
+
+/// Guess the fraction of bytes Tinify will save on an image, from `0.0` to `1.0`, without uploading it.
+///
+/// This is a heuristic read from the header only, meant to skip images that are unlikely to benefit before spending a compression on them, not a prediction of the result:
+/// - a JPEG is scored by the quality its quantization tables were saved with, an image saved at quality 75 or lower is expected to gain nothing.
+/// - a PNG is scored by its color type, truecolor images shrink the most once Tinify reduces their colors, palette images are already quantized.
+///
+/// Returns `None` for other formats and for headers it cannot read.
+pub fn estimate_savings(bytes: &[u8]) -> Option<f64> {
+  match detect_format(bytes)? {
+    DetectedFormat::Jpeg => {
+      let quality = f64::from(jpeg_quality(bytes)?);
+      Some(((quality - 75.0) / 25.0 * 0.5).clamp(0.0, 0.5))
+    }
+    DetectedFormat::Png => match (bytes.get(25)?, bytes.get(24)?) {
+      (3, _) => Some(0.1),
+      (0 | 4, _) => Some(0.3),
+      (2 | 6, 16) => Some(0.8),
+      (2 | 6, _) => Some(0.7),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/// Estimate the quality, from `1` to `100`, a JPEG was saved with from its first luminance quantization table.
+fn jpeg_quality(bytes: &[u8]) -> Option<u32> {
+  let mut offset = 2;
+
+  while let Some(header) = bytes.get(offset..offset + 4) {
+    if header[0] != 0xff || header[1] == 0xda {
+      return None;
+    }
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+
+    // Only 8-bit tables are considered, the precision is in the high nibble.
+    if header[1] == 0xdb && bytes.get(offset + 4)? & 0xf0 == 0 {
+      let table = bytes.get(offset + 5..offset + 69)?;
+      let sum = table.iter().map(|&value| u32::from(value)).sum::<u32>();
+      let scale = sum * 100 / JPEG_LUMINANCE_SUM;
+      let quality = if scale <= 100 {
+        (200 - scale) / 2
+      } else {
+        5000 / scale
+      };
+      return Some(quality.clamp(1, 100));
+    }
+    offset += 2 + length;
+  }
+
+  None
+}
+
+/// Check whether a PNG or WebP image can have transparent pixels.
+///
+/// A PNG with an alpha color type or a `tRNS` chunk, and a WebP with an alpha flag, count as transparent, even when every pixel happens to be opaque.
+pub fn has_alpha(bytes: &[u8]) -> bool {
+  match detect_format(bytes) {
+    Some(DetectedFormat::Png) => has_alpha_png(bytes),
+    Some(DetectedFormat::Webp) => has_alpha_webp(bytes),
+    _ => false,
+  }
+}
+
+fn has_alpha_png(bytes: &[u8]) -> bool {
+  if matches!(bytes.get(25), Some(4 | 6)) {
+    return true;
+  }
+  let mut offset = 8;
+
+  while let Some(header) = bytes.get(offset..offset + 8) {
+    let length =
+      u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+
+    match &header[4..8] {
+      b"tRNS" => return true,
+      b"IDAT" | b"IEND" => return false,
+      _ => offset += 12 + length as usize,
+    }
+  }
+
+  false
+}
+
+fn has_alpha_webp(bytes: &[u8]) -> bool {
+  match bytes.get(12..16) {
+    Some(b"VP8X") => bytes.len() >= 21 && bytes[20] & 0x10 != 0,
+    Some(b"VP8L") => bytes.len() >= 25 && bytes[24] & 0x10 != 0,
+    _ => false,
+  }
+}
+
+/// Read the EXIF orientation of a JPEG, PNG or WebP image, from `1` (upright) to `8`.
+///
+/// Orientations `5` to `8` are displayed rotated by 90 degrees, so the width and height seen by a viewer are swapped compared to the stored pixels. Returns `None` when the image has no EXIF orientation.
+pub fn exif_orientation(bytes: &[u8]) -> Option<u8> {
+  let tiff = match detect_format(bytes)? {
+    DetectedFormat::Jpeg => jpeg_exif(bytes)?,
+    DetectedFormat::Png => png_exif(bytes)?,
+    DetectedFormat::Webp => webp_exif(bytes)?,
+    _ => return None,
+  };
+  let tiff = tiff.strip_prefix(b"Exif\0\0").unwrap_or(tiff);
+
+  tiff_orientation(tiff).filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// The EXIF data of a JPEG is stored in an `APP1` segment before the image data.
+fn jpeg_exif(bytes: &[u8]) -> Option<&[u8]> {
+  let mut offset = 2;
+
+  while let Some(header) = bytes.get(offset..offset + 4) {
+    if header[0] != 0xff || header[1] == 0xda {
+      return None;
+    }
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let segment = bytes.get(offset + 4..offset + 2 + length)?;
+
+    if header[1] == 0xe1 && segment.starts_with(b"Exif\0\0") {
+      return Some(segment);
+    }
+    offset += 2 + length;
+  }
+
+  None
+}
+
+/// The EXIF data of a PNG is stored in an `eXIf` chunk.
+fn png_exif(bytes: &[u8]) -> Option<&[u8]> {
+  let mut offset = 8;
+
+  while let Some(header) = bytes.get(offset..offset + 8) {
+    let length =
+      u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+
+    match &header[4..8] {
+      b"eXIf" => return bytes.get(offset + 8..offset + 8 + length),
+      b"IEND" => return None,
+      _ => offset += 12 + length,
+    }
+  }
+
+  None
+}
+
+/// The EXIF data of a WebP is stored in an `EXIF` chunk, chunks are padded to an even size.
+fn webp_exif(bytes: &[u8]) -> Option<&[u8]> {
+  let mut offset = 12;
+
+  while let Some(header) = bytes.get(offset..offset + 8) {
+    let length =
+      u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+    if &header[..4] == b"EXIF" {
+      return bytes.get(offset + 8..offset + 8 + length);
+    }
+    offset += 8 + length + length % 2;
+  }
+
+  None
+}
+
+/// Find the orientation tag `0x0112` in the first IFD of a TIFF header.
+fn tiff_orientation(tiff: &[u8]) -> Option<u8> {
+  let u16_at = |offset: usize, little: bool| {
+    let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+    Some(if little {
+      u16::from_le_bytes(bytes)
+    } else {
+      u16::from_be_bytes(bytes)
+    })
+  };
+  let little = match tiff.get(..4)? {
+    b"II*\0" => true,
+    b"MM\0*" => false,
+    _ => return None,
+  };
+  let ifd = tiff.get(4..8)?;
+  let ifd = if little {
+    u32::from_le_bytes([ifd[0], ifd[1], ifd[2], ifd[3]])
+  } else {
+    u32::from_be_bytes([ifd[0], ifd[1], ifd[2], ifd[3]])
+  } as usize;
+  let entries = u16_at(ifd, little)? as usize;
+
+  (0..entries)
+    .map(|i| ifd + 2 + i * 12)
+    .find(|&entry| u16_at(entry, little) == Some(0x0112))
+    .and_then(|entry| u16_at(entry + 8, little))
+    .and_then(|orientation| u8::try_from(orientation).ok())
+}
+
+/// The extensions of the formats Tinify accepts as input.
+const SUPPORTED_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "avif"];
+
+/// Fail when a file doesn't have the extension of a format Tinify accepts, before reading it.
+pub(crate) fn check_extension(path: &Path) -> Result<(), TinifyError> {
+  let supported =
+    path
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .is_some_and(|ext| {
+        SUPPORTED_EXTENSIONS
+          .iter()
+          .any(|supported| ext.eq_ignore_ascii_case(supported))
+      });
+
+  if !supported {
+    let upstream = Upstream {
+      error: "Extension".to_string(),
+      message: format!(
+        "Unsupported file extension of `{}`, expected one of {}.",
+        path.display(),
+        SUPPORTED_EXTENSIONS.join(", ")
+      ),
+    };
+    return Err(TinifyError::ClientError { upstream });
+  }
+
+  Ok(())
+}
+
+/// Fail when the magic bytes of a file, whose extension was accepted, aren't those of a format Tinify accepts.
+pub(crate) fn check_content(
+  path: &Path,
+  bytes: &[u8],
+) -> Result<(), TinifyError> {
+  match detect_format(bytes) {
+    Some(DetectedFormat::Gif) | None => {
+      let upstream = Upstream {
+        error: "Format".to_string(),
+        message: format!(
+          "The content of `{}` is not a PNG, JPEG, WebP or AVIF image.",
+          path.display()
+        ),
+      };
+      Err(TinifyError::ClientError { upstream })
+    }
+    Some(_) => Ok(()),
+  }
+}
+
+/// Fail when the body an origin answered with isn't a PNG, JPEG, WebP or AVIF image, like a login page, before it is uploaded.
+///
+/// The `Content-Type` decides, only a missing or `application/octet-stream` one falls back to the magic bytes.
+pub(crate) fn check_source_type(
+  content_type: Option<&str>,
+  bytes: &[u8],
+) -> Result<(), TinifyError> {
+  let mime = content_type
+    .map(|content_type| {
+      content_type.split(';').next().unwrap_or_default().trim()
+    })
+    .filter(|mime| {
+      !mime.is_empty() && !mime.eq_ignore_ascii_case("application/octet-stream")
+    });
+  let format = match mime {
+    Some(mime) => DetectedFormat::from_mime_type(mime),
+    None => detect_format(bytes),
+  };
+
+  match format {
+    Some(DetectedFormat::Gif) | None => {
+      let sent = match mime {
+        Some(mime) => format!("`{}`", mime),
+        None => "an unknown type".to_string(),
+      };
+      let upstream = Upstream {
+        error: "Format".to_string(),
+        message: format!(
+          "The origin answered with {}, not a PNG, JPEG, WebP or AVIF image.",
+          sent
+        ),
+      };
+      Err(TinifyError::ClientError { upstream })
+    }
+    Some(_) => Ok(()),
+  }
+}
+
+/// Fail when a downloaded result isn't a PNG, JPEG, WebP or AVIF image with a readable header, like an error page served with a success status.
+pub(crate) fn check_output(bytes: &[u8]) -> Result<(), TinifyError> {
+  let valid = match detect_format(bytes) {
+    Some(DetectedFormat::Avif) => true,
+    Some(DetectedFormat::Gif) | None => false,
+    Some(_) => image_size(bytes).is_some(),
+  };
+
+  if !valid {
+    let upstream = Upstream {
+      error: "Output".to_string(),
+      message: format!(
+        "The compressed image is not a valid PNG, JPEG, WebP or AVIF image, got {} bytes.",
+        bytes.len()
+      ),
+    };
+    return Err(TinifyError::ServerError {
+      upstream,
+      status: None,
+    });
+  }
+
+  Ok(())
+}
+
+impl TryFrom<&[u8]> for DetectedFormat {
+  type Error = TinifyError;
+
+  fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+    detect_format(bytes).ok_or_else(|| {
+      let upstream = Upstream {
+        error: "Format".to_string(),
+        message: "The image format could not be detected.".to_string(),
+      };
+      TinifyError::ClientError { upstream }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::tiny_png;
+  use assert_matches::assert_matches;
+
+  #[test]
+  fn test_detect_png() {
+    let bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    assert_eq!(detect_format(bytes), Some(DetectedFormat::Png));
+  }
+
+  #[test]
+  fn test_mime_type() {
+    assert_eq!(DetectedFormat::Png.mime_type(), "image/png");
+    assert_eq!(DetectedFormat::Avif.mime_type(), "image/avif");
+  }
+
+  #[test]
+  fn test_detect_jpeg() -> Result<(), TinifyError> {
+    let bytes = std::fs::read("./tmp_image.jpg")?;
+
+    assert_eq!(detect_format(&bytes), Some(DetectedFormat::Jpeg));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_detect_webp() {
+    let bytes = b"RIFF\x24\0\0\0WEBPVP8 ";
+
+    assert_eq!(detect_format(bytes), Some(DetectedFormat::Webp));
+  }
+
+  #[test]
+  fn test_detect_gif() {
+    assert_eq!(detect_format(b"GIF89a\x01\0"), Some(DetectedFormat::Gif));
+    assert_eq!(detect_format(b"GIF87a\x01\0"), Some(DetectedFormat::Gif));
+  }
+
+  #[test]
+  fn test_detect_avif() {
+    let major = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf";
+    let compatible = b"\0\0\0\x1cftypmif1\0\0\0\0mif1avifmiaf";
+
+    assert_eq!(detect_format(major), Some(DetectedFormat::Avif));
+    assert_eq!(detect_format(compatible), Some(DetectedFormat::Avif));
+    assert_eq!(detect_format(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic"), None);
+  }
+
+  fn chunk(r#type: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(r#type);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&[0; 4]);
+    chunk
+  }
+
+  #[test]
+  fn test_animated_png() {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend(chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]));
+    let mut apng = png.clone();
+    apng.extend(chunk(b"acTL", &[0, 0, 0, 2, 0, 0, 0, 0]));
+    apng.extend(chunk(b"IDAT", &[0; 4]));
+    png.extend(chunk(b"IDAT", &[0; 4]));
+    png.extend(chunk(b"acTL", &[0, 0, 0, 2, 0, 0, 0, 0]));
+
+    assert!(is_animated(&apng));
+    assert!(!is_animated(&png));
+  }
+
+  #[test]
+  fn test_animated_webp() {
+    let mut webp = b"RIFF\x1a\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+    let mut animated = webp.clone();
+    webp.extend_from_slice(&[0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    animated.extend_from_slice(&[0x12, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    assert!(is_animated(&animated));
+    assert!(!is_animated(&webp));
+    assert!(!is_animated(b"RIFF\x24\0\0\0WEBPVP8 "));
+  }
+
+  #[test]
+  fn test_detect_unknown() {
+    assert_eq!(detect_format(b""), None);
+    assert_eq!(detect_format(b"<!DOCTYPE html>"), None);
+    assert_eq!(detect_format(b"RIFF\0\0\0\0WAVE"), None);
+    assert_matches!(
+      DetectedFormat::try_from(&b"<html>"[..]),
+      Err(TinifyError::ClientError { .. })
+    );
+  }
+
+  fn tiff(orientation: u16) -> Vec<u8> {
+    let mut tiff = b"MM\0*\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    tiff.extend_from_slice(&[0; 6]);
+    tiff
+  }
+
+  #[test]
+  fn test_exif_orientation_jpeg() {
+    let exif = [b"Exif\0\0".as_slice(), &tiff(6)].concat();
+    let length = (exif.len() as u16 + 2).to_be_bytes();
+    let bytes = [
+      &[0xff, 0xd8, 0xff, 0xe1, length[0], length[1]],
+      exif.as_slice(),
+      &[0xff, 0xda],
+    ]
+    .concat();
+
+    assert_eq!(exif_orientation(&bytes), Some(6));
+  }
+
+  #[test]
+  fn test_exif_orientation_png() {
+    let exif = tiff(3);
+    let bytes = [
+      b"\x89PNG\r\n\x1a\n".as_slice(),
+      &(exif.len() as u32).to_be_bytes(),
+      b"eXIf",
+      &exif,
+      &[0; 4],
+    ]
+    .concat();
+
+    assert_eq!(exif_orientation(&bytes), Some(3));
+  }
+
+  #[test]
+  fn test_exif_orientation_missing() -> Result<(), TinifyError> {
+    let bytes = std::fs::read("./tmp_image.jpg")?;
+
+    assert_eq!(exif_orientation(b"\x89PNG\r\n\x1a\n"), None);
+    assert_eq!(exif_orientation(&bytes), None);
+    assert_eq!(exif_orientation(&tiff(9)), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_has_alpha() {
+    let png = |color_type: u8| {
+      let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+      bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 8, color_type]);
+      bytes.extend_from_slice(&[0; 7]);
+      bytes
+    };
+    let trns = [png(3).as_slice(), b"\0\0\0\x01tRNS\0", &[0; 4]].concat();
+    let webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x10";
+
+    assert!(has_alpha(&png(6)));
+    assert!(has_alpha(&trns));
+    assert!(has_alpha(webp));
+    assert!(!has_alpha(&png(2)));
+    assert!(!has_alpha(&[0xff, 0xd8, 0xff]));
+  }
+
+  #[test]
+  fn test_check_extension() -> Result<(), TinifyError> {
+    check_extension(Path::new("./images/panda.PNG"))?;
+    check_extension(Path::new("photo.jpeg"))?;
+
+    for path in ["animation.gif", "scan.tiff", "README"] {
+      assert_matches!(
+        check_extension(Path::new(path)),
+        Err(TinifyError::ClientError { .. })
+      );
+    }
+    assert_matches!(
+      check_content(Path::new("fake.png"), b"GIF89a"),
+      Err(TinifyError::ClientError { .. })
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_image_size() -> Result<(), TinifyError> {
+    let jpeg = std::fs::read("./tmp_image.jpg")?;
+    let expected = imagesize::blob_size(&jpeg).unwrap();
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x03\x20\0\0\x01\x90";
+    let webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0\x1f\x03\0\x8f\x01\0";
+
+    assert_eq!(
+      image_size(&jpeg),
+      Some((expected.width as u32, expected.height as u32))
+    );
+    assert_eq!(image_size(png), Some((800, 400)));
+    assert_eq!(image_size(webp), Some((800, 400)));
+    assert_eq!(image_size(b"\x89PNG\r\n\x1a\n"), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_estimate_savings() -> Result<(), TinifyError> {
+    let png = |color: u8, depth: u8| {
+      let mut png =
+        b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\x01\0\0\0\x01\0".to_vec();
+      png.extend([depth, color]);
+      png
+    };
+    let jpeg = |value: u8| {
+      let mut jpeg = b"\xff\xd8\xff\xdb\0\x43\0".to_vec();
+      jpeg.extend([value; 64]);
+      jpeg
+    };
+    let fixture = std::fs::read("./tmp_image.jpg")?;
+
+    assert_eq!(estimate_savings(&png(6, 8)), Some(0.7));
+    assert_eq!(estimate_savings(&png(3, 8)), Some(0.1));
+    assert!(estimate_savings(&jpeg(1)).is_some_and(|savings| savings > 0.45));
+    assert_eq!(estimate_savings(&jpeg(200)), Some(0.0));
+    assert!(estimate_savings(&fixture).is_some_and(|savings| savings > 0.0));
+    assert_eq!(estimate_savings(b"GIF89a\x01\0\x01\0"), None);
+    assert_eq!(estimate_savings(b"\xff\xd8\xff\xda\0\x02"), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_check_output() {
+    let png = tiny_png();
+
+    assert!(check_output(png).is_ok());
+    for bytes in [
+      &b"<html><body>Bad Gateway</body></html>"[..],
+      &b"\x89PNG\r\n\x1a\n"[..],
+      &b"GIF89a\x01\0\x01\0"[..],
+      &b""[..],
+    ] {
+      assert_matches!(
+        check_output(bytes),
+        Err(TinifyError::ServerError { ref upstream, .. }) if upstream.error == "Output"
+      );
+    }
+  }
+
+  #[test]
+  fn test_check_source_type() {
+    let png = b"\x89PNG\r\n\x1a\n";
+
+    assert!(check_source_type(Some("image/png"), b"").is_ok());
+    assert!(check_source_type(Some("IMAGE/WEBP; q=1"), b"").is_ok());
+    assert!(check_source_type(None, png).is_ok());
+    assert!(check_source_type(Some("application/octet-stream"), png).is_ok());
+    assert_matches!(
+      check_source_type(Some("text/html; charset=utf-8"), png),
+      Err(TinifyError::ClientError { ref upstream })
+        if upstream.error == "Format" && upstream.message.contains("`text/html`")
+    );
+    assert_matches!(
+      check_source_type(Some("image/gif"), b""),
+      Err(TinifyError::ClientError { .. })
+    );
+    assert_matches!(
+      check_source_type(None, b"<html>"),
+      Err(TinifyError::ClientError { ref upstream })
+        if upstream.message.contains("an unknown type")
+    );
+  }
+
+  #[test]
+  fn test_format_from_mime_type() {
+    assert_eq!(
+      DetectedFormat::from_mime_type("image/webp"),
+      Some(DetectedFormat::Webp)
+    );
+    assert_eq!(
+      DetectedFormat::from_mime_type("IMAGE/AVIF; charset=binary"),
+      Some(DetectedFormat::Avif)
+    );
+    assert_eq!(DetectedFormat::from_mime_type("text/html"), None);
+  }
+}
@@ -0,0 +1,78 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use image::ImageFormat;
+use std::io::Cursor;
+
+fn gif_error<E>(err: E) -> TinifyError
+where
+  E: ToString,
+{
+  let upstream = Upstream {
+    error: "Gif".to_string(),
+    message: err.to_string(),
+  };
+  TinifyError::ClientError { upstream }
+}
+
+/// Decode the first frame of a GIF, animated or not, and encode it as a PNG.
+pub(crate) fn first_frame(gif: &[u8]) -> Result<Vec<u8>, TinifyError> {
+  if !gif.starts_with(b"GIF87a") && !gif.starts_with(b"GIF89a") {
+    return Err(gif_error("The input is not a GIF image."));
+  }
+
+  let frame = image::load_from_memory_with_format(gif, ImageFormat::Gif)
+    .map_err(gif_error)?;
+  let mut png = Cursor::new(Vec::new());
+  frame
+    .write_to(&mut png, ImageFormat::Png)
+    .map_err(gif_error)?;
+
+  Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::tiny_png;
+  use assert_matches::assert_matches;
+  use image::codecs::gif::GifEncoder;
+  use image::Frame;
+  use image::Rgba;
+  use image::RgbaImage;
+
+  #[test]
+  fn test_first_frame() -> Result<(), TinifyError> {
+    let mut gif = Vec::new();
+    {
+      let mut encoder = GifEncoder::new(&mut gif);
+      for color in [Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255])] {
+        let frame = Frame::new(RgbaImage::from_pixel(6, 3, color));
+        encoder.encode_frame(frame).map_err(gif_error)?;
+      }
+    }
+
+    let png = first_frame(&gif)?;
+    let frame = image::load_from_memory_with_format(&png, ImageFormat::Png)
+      .map_err(gif_error)?
+      .to_rgba8();
+
+    assert_eq!(frame.dimensions(), (6, 3));
+    assert_eq!(frame.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_first_frame_of_non_gif() {
+    let png = tiny_png();
+
+    assert_matches!(
+      first_frame(png),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Gif"
+    );
+    assert_matches!(
+      first_frame(b"GIF89a truncated"),
+      Err(TinifyError::ClientError { upstream }) if upstream.error == "Gif"
+    );
+  }
+}
@@ -0,0 +1,74 @@
+use crate::cache;
+
+/// A 64-bit FNV-1a hash, stable across platforms and releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+  })
+}
+
+/// The hash of a compressed image returned by [`compress_and_hash`](crate::prelude::Client::compress_and_hash), for cache-busting file names like `logo.ab12cd34.webp`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+  /// The 64-bit FNV-1a hash, fast and stable across releases, as 16 hex digits. It is no cryptographic hash, collisions can be crafted.
+  #[default]
+  Fnv1a,
+
+  /// SHA-256, as 64 hex digits.
+  Sha256,
+}
+
+impl HashAlgorithm {
+  /// Hash `bytes` as lowercase hex digits.
+  pub fn hex(self, bytes: &[u8]) -> String {
+    match self {
+      HashAlgorithm::Fnv1a => format!("{:016x}", fnv1a(bytes)),
+      HashAlgorithm::Sha256 => cache::hex(&cache::digest(bytes)),
+    }
+  }
+}
+
+/// A compressed image with the hash of its bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashedImage {
+  /// The bytes of the compressed image.
+  pub bytes: Vec<u8>,
+
+  /// The hash of `bytes` as lowercase hex digits, shorten it as needed for file names.
+  pub hash: String,
+}
+
+impl HashedImage {
+  pub(crate) fn new(bytes: Vec<u8>, algorithm: HashAlgorithm) -> Self {
+    let hash = algorithm.hex(&bytes);
+
+    Self { bytes, hash }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fnv1a_hex() {
+    assert_eq!(HashAlgorithm::Fnv1a.hex(b""), "cbf29ce484222325");
+    assert_eq!(HashAlgorithm::Fnv1a.hex(b"a"), "af63dc4c8601ec8c");
+  }
+
+  #[test]
+  fn test_sha256_hex() {
+    assert_eq!(
+      HashAlgorithm::Sha256.hex(b"abc"),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+  }
+
+  #[test]
+  fn test_hashed_image() {
+    let image = HashedImage::new(b"a".to_vec(), HashAlgorithm::default());
+
+    assert_eq!(image.bytes, b"a");
+    assert_eq!(image.hash, "af63dc4c8601ec8c");
+  }
+}
@@ -0,0 +1,109 @@
+#[cfg(feature = "archive")]
+use crate::error::TinifyError;
+use crate::path_with_suffix;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+/// How [`compress_file_in_place`](crate::prelude::Client::compress_file_in_place) treats the original file, there is no default so replacing it is always explicit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InPlace {
+  /// Replace the file with its compressed version.
+  Overwrite,
+
+  /// Copy the file next to itself with `.orig` appended, like `logo.png.orig`, then replace it.
+  ///
+  /// An existing backup is kept, so compressing the same file again never replaces the original with an already compressed version.
+  Backup,
+}
+
+/// The sizes of a file or buffer compressed in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InPlaceReport {
+  /// The size in bytes of the original file.
+  pub original_size: usize,
+
+  /// The size in bytes of the compressed file that replaced it.
+  pub compressed_size: usize,
+}
+
+impl InPlaceReport {
+  /// Get the number of bytes saved, `0` when the compressed file is not smaller.
+  pub fn bytes_saved(&self) -> usize {
+    self.original_size.saturating_sub(self.compressed_size)
+  }
+}
+
+/// The images of an archive compressed by [`compress_archive`](crate::prelude::Client::compress_archive), each named as in the archive with its sizes or its own error.
+#[cfg(feature = "archive")]
+pub type ArchiveReport = Vec<(String, Result<InPlaceReport, TinifyError>)>;
+
+/// Copy `path` to its backup, with `.orig` appended to its file name, unless a backup from an earlier run exists, which holds the actual original.
+///
+/// A failed copy removes the partial backup again.
+pub(crate) fn back_up(path: &Path) -> io::Result<()> {
+  let backup = path_with_suffix(path, ".orig");
+  let mut file = match OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .open(&backup)
+  {
+    Ok(file) => file,
+    Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Ok(()),
+    Err(err) => return Err(err),
+  };
+  let copied = File::open(path)
+    .and_then(|mut original| io::copy(&mut original, &mut file))
+    .and_then(|_| file.sync_all());
+
+  if copied.is_err() {
+    let _ = fs::remove_file(&backup);
+  }
+  copied
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn test_back_up_keeps_existing_backup() -> io::Result<()> {
+    let dir = env::temp_dir().join("tinify_back_up");
+    let path = dir.join("logo.png");
+    fs::create_dir_all(&dir)?;
+
+    fs::write(&path, b"original")?;
+    back_up(&path)?;
+    fs::write(&path, b"compressed")?;
+    back_up(&path)?;
+
+    assert_eq!(fs::read(dir.join("logo.png.orig"))?, b"original");
+    assert!(back_up(&dir.join("missing.png")).is_err());
+    assert!(!dir.join("missing.png.orig").exists());
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_bytes_saved() {
+    let report = InPlaceReport {
+      original_size: 124814,
+      compressed_size: 102051,
+    };
+
+    assert_eq!(report.bytes_saved(), 22763);
+    assert_eq!(
+      InPlaceReport {
+        original_size: 10,
+        compressed_size: 12,
+      }
+      .bytes_saved(),
+      0
+    );
+  }
+}
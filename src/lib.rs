@@ -6,29 +6,138 @@
 //! Read more at `https://tinify.com`
 // --snip--
 
+use cache::Cache;
 use convert::Convert;
+use convert::Type;
+use error::TinifyError;
+use error::Upstream;
+use limit::Limiter;
+use preserve::Metadata;
+use preserve::Preserve;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest::header::CONTENT_DISPOSITION;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::LOCATION;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use resize::Method;
 use resize::Resize;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::SystemTime;
+use store::Store;
+use transform::Background;
 use transform::Transform;
+use url::Url;
 
+pub mod account;
+#[cfg(feature = "archive")]
+mod archive;
 #[cfg(feature = "async")]
 pub mod async_bin;
+mod cache;
 pub mod convert;
 pub mod error;
+#[cfg(test)]
+mod fixture;
+pub mod format;
+#[cfg(feature = "gif")]
+mod gif;
+pub mod hash;
+pub mod in_place;
+mod limit;
+pub mod location;
+#[cfg(test)]
+mod mock;
+pub mod plan;
+pub mod prelude;
+pub mod preserve;
 pub mod resize;
+pub mod state;
+pub mod store;
+#[cfg(feature = "svg")]
+mod svg;
 #[cfg(not(feature = "async"))]
 pub mod sync;
+pub mod template;
 pub mod transform;
 
 pub(crate) const API_ENDPOINT: &str = "https://api.tinify.com";
 
+/// The timeout of every request, unless a shorter one is configured.
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How much of a file is read between two progress reports, 1 MB.
+const READ_CHUNK: u64 = 1024 * 1024;
+
+/// How much of an error response is read by default, 64 KB.
+pub(crate) const DEFAULT_ERROR_BODY_LIMIT: usize = 64 * 1024;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct SourceUrl {
   url: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Settings shared by a `Tinify` builder, its clients and their sources.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Config {
+  pub(crate) cache: Option<Cache>,
+  pub(crate) source_fetch_timeout: Option<Duration>,
+  pub(crate) reject_animated: bool,
+  pub(crate) reject_unsupported_extensions: bool,
+  pub(crate) error_body_limit: Option<usize>,
+  pub(crate) dry_run: bool,
+  pub(crate) reject_upscale: bool,
+  pub(crate) create_dirs: bool,
+  pub(crate) keep_original_if_larger: bool,
+  pub(crate) verify_output: bool,
+  pub(crate) concurrency: Option<Limiter>,
+  pub(crate) dedup_batches: bool,
+  pub(crate) pool_max_idle_per_host: Option<usize>,
+  pub(crate) no_overwrite: bool,
+  pub(crate) default_operations: Operations,
+  pub(crate) default_background: Option<Background>,
+  pub(crate) endpoint: Option<Url>,
+}
+
+impl Config {
+  pub(crate) fn error_body_limit(&self) -> usize {
+    self.error_body_limit.unwrap_or(DEFAULT_ERROR_BODY_LIMIT)
+  }
+
+  /// The endpoint requests are sent to, `https://api.tinify.com` unless another one was set with `Tinify::set_endpoint`.
+  pub(crate) fn endpoint(&self) -> Result<Url, TinifyError> {
+    match &self.endpoint {
+      Some(endpoint) => Ok(endpoint.clone()),
+      None => Ok(Url::parse(API_ENDPOINT)?),
+    }
+  }
+}
+
+/// The operations applied to a compressed image.
+///
+/// The fields serialize in declaration order, so the request body (and the cache key built from it) is always `convert`, `resize`, `transform`, then `preserve`. Keep that order when adding operations. The raw operations follow, sorted by name.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub(crate) struct Operations {
   #[serde(skip_serializing_if = "Option::is_none")]
   convert: Option<Convert>,
@@ -38,4 +147,1280 @@ pub(crate) struct Operations {
 
   #[serde(skip_serializing_if = "Option::is_none")]
   transform: Option<Transform>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  preserve: Option<Preserve>,
+
+  #[serde(flatten)]
+  raw: BTreeMap<String, serde_json::Value>,
+}
+
+impl Operations {
+  pub(crate) fn is_empty(&self) -> bool {
+    self.convert.is_none()
+      && self.resize.is_none()
+      && self.transform.is_none()
+      && self.preserve.is_none()
+      && self.raw.is_empty()
+  }
+
+  /// Add an operation by its name in the API, for operations without a typed method yet.
+  pub(crate) fn set_raw(
+    &mut self,
+    name: &str,
+    value: serde_json::Value,
+  ) -> Result<(), TinifyError> {
+    let message = if matches!(
+      name,
+      "convert" | "resize" | "transform" | "preserve" | "store"
+    ) {
+      format!(
+        "Use the `{}` method for the `{}` operation, raw operations are only for operations without one.",
+        name, name
+      )
+    } else if value.is_null() {
+      format!("The `{}` operation has no value.", name)
+    } else {
+      self.raw.insert(name.to_string(), value);
+      return Ok(());
+    };
+    let upstream = Upstream {
+      error: "Operation".to_string(),
+      message,
+    };
+
+    Err(TinifyError::ClientError { upstream })
+  }
+
+  /// Whether the operations convert to JPEG only, which has no transparency.
+  fn converts_to_jpeg_only(&self) -> bool {
+    self.convert.as_ref().is_some_and(|convert| {
+      !convert.r#type.is_empty()
+        && convert
+          .r#type
+          .iter()
+          .all(|r#type| matches!(r#type, Type::Jpeg))
+    })
+  }
+
+  /// Fill the transparency with `background` when converting to JPEG only without a `Transform` of its own.
+  pub(crate) fn with_default_background(
+    mut self,
+    background: Option<Background>,
+  ) -> Self {
+    if let Some(background) = background {
+      if self.transform.is_none() && self.converts_to_jpeg_only() {
+        self.transform = Some(Transform { background });
+      }
+    }
+
+    self
+  }
+
+  /// Fail when a transparent input is converted to JPEG only, without a background to fill the transparency with.
+  pub(crate) fn check_background(
+    &self,
+    transparent: bool,
+  ) -> Result<(), TinifyError> {
+    if transparent && self.converts_to_jpeg_only() && self.transform.is_none() {
+      let upstream = Upstream {
+        error: "Transparency".to_string(),
+        message: "JPEG output requires a background for transparent input, add a `Transform` with a `Background`.".to_string(),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+
+    Ok(())
+  }
+
+  /// Fail when a resize doesn't have the dimensions its method needs: exactly one of `width` and `height` to scale, both of them otherwise, none of them `0`.
+  pub(crate) fn check_resize(&self) -> Result<(), TinifyError> {
+    if let Some(resize) = &self.resize {
+      let valid = match resize.method {
+        Method::Scale => resize.width.is_some() != resize.height.is_some(),
+        Method::Fit | Method::Cover | Method::Thumb => {
+          resize.width.is_some() && resize.height.is_some()
+        }
+      };
+
+      if !valid {
+        let needs = match resize.method {
+          Method::Scale => "exactly one of `width` and `height`",
+          _ => "both a `width` and a `height`",
+        };
+        let upstream = Upstream {
+          error: "Resize".to_string(),
+          message: format!(
+            "Resizing with `{}` needs {}.",
+            method_name(&resize.method),
+            needs
+          ),
+        };
+        return Err(TinifyError::ClientError { upstream });
+      }
+
+      if resize.width == Some(0) || resize.height == Some(0) {
+        let upstream = Upstream {
+          error: "Resize".to_string(),
+          message: format!(
+            "Resizing with `{}` needs a size above 0.",
+            method_name(&resize.method)
+          ),
+        };
+        return Err(TinifyError::ClientError { upstream });
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Fail when a resize would upscale the input, Tinify only scales images down.
+  ///
+  /// Only a `scale` target larger than its side of the input, or a `fit` box larger than the input on both sides, is an upscale. A `fit` box larger on one side only still scales down, and `cover` and `thumb` crop to their box instead. The input is compared as displayed, with the width and height swapped for the EXIF `orientation` `5` to `8`.
+  pub(crate) fn check_upscale(
+    &self,
+    input: Option<(u32, u32)>,
+    orientation: Option<u8>,
+  ) -> Result<(), TinifyError> {
+    if let (Some(resize), Some((width, height))) = (&self.resize, input) {
+      let (width, height) = match orientation {
+        Some(5..=8) => (height, width),
+        _ => (width, height),
+      };
+      let wider = resize.width.is_some_and(|target| target > width);
+      let taller = resize.height.is_some_and(|target| target > height);
+      let upscale = match resize.method {
+        Method::Scale => wider || taller,
+        Method::Fit => wider && taller,
+        Method::Cover | Method::Thumb => false,
+      };
+
+      if upscale {
+        let upstream = Upstream {
+          error: "Upscale".to_string(),
+          message: format!(
+            "Resizing a {}x{} image to{} would upscale it, Tinify only scales images down.",
+            width,
+            height,
+            resize_size(resize)
+          ),
+        };
+        return Err(TinifyError::ClientError { upstream });
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// The name of a resize method in the API, like `fit`.
+fn method_name(method: &Method) -> &'static str {
+  match method {
+    Method::Scale => "scale",
+    Method::Fit => "fit",
+    Method::Cover => "cover",
+    Method::Thumb => "thumb",
+  }
+}
+
+/// The target size of a resize, like ` 400x200`, ` width 400` or ` height 200`.
+fn resize_size(resize: &Resize) -> String {
+  match (resize.width, resize.height) {
+    (Some(width), Some(height)) => format!(" {}x{}", width, height),
+    (Some(width), None) => format!(" width {}", width),
+    (None, Some(height)) => format!(" height {}", height),
+    (None, None) => String::new(),
+  }
+}
+
+/// A short summary like `resize(fit 400x200) + convert(webp)`, `none` without operations.
+///
+/// The operations are always listed as `resize`, `convert`, `transform`, `preserve`, then the raw operations by name, so the summary is stable for logs and snapshot tests.
+impl fmt::Display for Operations {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut parts = Vec::new();
+
+    if let Some(resize) = &self.resize {
+      parts.push(format!(
+        "resize({}{})",
+        method_name(&resize.method),
+        resize_size(resize)
+      ));
+    }
+    if let Some(convert) = &self.convert {
+      let types: Vec<&str> = convert
+        .r#type
+        .iter()
+        .map(|r#type| r#type.mime_type().rsplit('/').next().unwrap_or("*"))
+        .collect();
+      parts.push(format!("convert({})", types.join("|")));
+    }
+    if let Some(transform) = &self.transform {
+      parts.push(format!("transform(background {})", transform.background));
+    }
+    if let Some(preserve) = &self.preserve {
+      let metadata: Vec<&str> = preserve
+        .metadata
+        .iter()
+        .map(|metadata| match metadata {
+          Metadata::Copyright => "copyright",
+          Metadata::Creation => "creation",
+          Metadata::Location => "location",
+        })
+        .collect();
+      parts.push(format!("preserve({})", metadata.join(", ")));
+    }
+    for (name, value) in &self.raw {
+      parts.push(format!("{}({})", name, value));
+    }
+
+    if parts.is_empty() {
+      f.write_str("none")
+    } else {
+      f.write_str(&parts.join(" + "))
+    }
+  }
+}
+
+/// The body of a `Source::store`, the pending operations and the `store` sent in the same request.
+#[derive(Serialize)]
+pub(crate) struct StoreOperations<'a> {
+  #[serde(flatten)]
+  pub(crate) operations: &'a Operations,
+
+  pub(crate) store: &'a Store,
+}
+
+/// The `Compression-Count` of the latest response, shared by a client and its sources.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CompressionCount(Arc<Mutex<Option<u32>>>);
+
+impl CompressionCount {
+  pub(crate) fn update(&self, headers: &HeaderMap) {
+    let count = headers
+      .get("compression-count")
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.trim().parse::<u32>().ok());
+
+    if let Some(count) = count {
+      *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(count);
+    }
+  }
+
+  pub(crate) fn get(&self) -> Option<u32> {
+    *self.0.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+}
+
+/// Parse the error of a response body, read up to `limit` bytes plus one to tell whether it was cut.
+///
+/// A body over the limit is not parsed, the message keeps its first `limit` bytes instead.
+pub(crate) fn parse_upstream(
+  body: &[u8],
+  limit: usize,
+) -> Result<Upstream, TinifyError> {
+  if body.len() > limit {
+    let upstream = Upstream {
+      error: "Truncated".to_string(),
+      message: format!(
+        "{}... (error response truncated after {} bytes)",
+        String::from_utf8_lossy(&body[..limit]),
+        limit
+      ),
+    };
+    return Ok(upstream);
+  }
+
+  Ok(serde_json::from_slice(body)?)
+}
+
+/// Read how long to wait before retrying from the `Retry-After` header, given in seconds or as an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+  let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+  parse_retry_after(value, SystemTime::now())
+}
+
+/// Parse a `Retry-After` value relative to `now`, a date in the past means no wait.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+  let value = value.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let date = httpdate::parse_http_date(value).ok()?;
+
+  Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Classify the status of a response that should be one of `expected`.
+///
+/// `429` is `TooManyRequests`, any other `4xx` a `ClientError` and the rest a `ServerError`,
+/// with the error parsed from `body` or its text kept as the message.
+pub(crate) fn classify_status(
+  status: StatusCode,
+  expected: &[StatusCode],
+  retry_after: Option<Duration>,
+  body: &[u8],
+  limit: usize,
+) -> Result<(), TinifyError> {
+  if expected.contains(&status) {
+    return Ok(());
+  }
+  if status.is_success() {
+    let expected: Vec<String> =
+      expected.iter().map(StatusCode::to_string).collect();
+    let upstream = Upstream {
+      error: "Status".to_string(),
+      message: format!(
+        "Expected status {}, got {status}.",
+        expected.join(" or ")
+      ),
+    };
+    return Err(TinifyError::ServerError {
+      upstream,
+      status: Some(status),
+    });
+  }
+
+  let upstream = parse_upstream(body, limit).unwrap_or_else(|_| Upstream {
+    error: status.canonical_reason().unwrap_or("Status").to_string(),
+    message: String::from_utf8_lossy(body).trim().to_string(),
+  });
+  if status == StatusCode::TOO_MANY_REQUESTS {
+    Err(TinifyError::TooManyRequests {
+      upstream,
+      retry_after,
+    })
+  } else if status.is_client_error() {
+    Err(TinifyError::ClientError { upstream })
+  } else {
+    Err(TinifyError::ServerError {
+      upstream,
+      status: Some(status),
+    })
+  }
+}
+
+/// Create the missing parent directories of `path`, the error names `path`.
+pub(crate) fn create_parent_dirs(path: &Path) -> Result<(), TinifyError> {
+  match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => {
+      fs::create_dir_all(parent).map_err(|err| {
+        io::Error::new(
+          err.kind(),
+          format!(
+            "failed to create the directory of `{}`: {}",
+            path.display(),
+            err
+          ),
+        )
+      })?;
+      Ok(())
+    }
+    _ => Ok(()),
+  }
+}
+
+/// The path of `path` with `suffix` appended to its file name, like the sidecar `optimized.png.json` or the backup `logo.png.orig`.
+pub(crate) fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+  file_name.push(suffix);
+
+  path.with_file_name(file_name)
+}
+
+/// Read the file at `path`, calling `progress` with the bytes read so far and the size of the file, first with none read and then after every chunk.
+pub(crate) fn read_file<F>(
+  path: &Path,
+  mut progress: F,
+) -> Result<Vec<u8>, TinifyError>
+where
+  F: FnMut(u64, u64),
+{
+  let mut file = File::open(path)?;
+  let size = file.metadata()?.len();
+  let mut buffer = Vec::with_capacity(size as usize);
+  progress(0, size);
+
+  loop {
+    let read = (&mut file).take(READ_CHUNK).read_to_end(&mut buffer)?;
+    if read == 0 {
+      break;
+    }
+    let len = buffer.len() as u64;
+    progress(len, size.max(len));
+  }
+
+  Ok(buffer)
+}
+
+/// Write `bytes` to `path` through a temporary file in the same directory, renamed over `path` once complete.
+///
+/// A failed write removes the temporary file and leaves an existing file at `path` untouched, the error names `path`.
+pub(crate) fn write_atomic(
+  path: &Path,
+  bytes: &[u8],
+) -> Result<(), TinifyError> {
+  write_through_tmp(path, bytes, true)
+}
+
+/// Write a file for the user like [`write_atomic`], creating its parent directories with `Tinify::create_dirs`, and failing with an `AlreadyExists` IO error when `path` exists with `Tinify::overwrite(false)`.
+///
+/// Every output goes through here: `to_file`, the sidecar of `save_both`, a deduplicated file of a batch and an archive. Without overwriting, the temporary file is hard linked to `path` instead of renamed over it, so a file created at `path` in the meantime is never replaced either.
+pub(crate) fn write_output(
+  config: &Config,
+  path: &Path,
+  bytes: &[u8],
+) -> Result<(), TinifyError> {
+  if config.create_dirs {
+    create_parent_dirs(path)?;
+  }
+
+  write_through_tmp(path, bytes, !config.no_overwrite)
+}
+
+fn write_through_tmp(
+  path: &Path,
+  bytes: &[u8],
+  overwrite: bool,
+) -> Result<(), TinifyError> {
+  static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+  let with_path = |err: io::Error| {
+    io::Error::new(
+      err.kind(),
+      format!("failed to write `{}`: {}", path.display(), err),
+    )
+  };
+  let file_name = path.file_name().ok_or_else(|| {
+    with_path(io::Error::new(io::ErrorKind::InvalidInput, "not a file"))
+  })?;
+  let mut tmp_name = OsString::from(".");
+  tmp_name.push(file_name);
+  tmp_name.push(format!(
+    ".{}-{}.tmp",
+    process::id(),
+    COUNTER.fetch_add(1, Ordering::Relaxed)
+  ));
+  let tmp = path.with_file_name(tmp_name);
+
+  let written = File::create(&tmp).and_then(|file| {
+    let mut writer = BufWriter::new(file);
+    writer.write_all(bytes)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+    if overwrite {
+      fs::rename(&tmp, path)
+    } else {
+      match fs::hard_link(&tmp, path) {
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+          write_new(path, bytes)
+        }
+        linked => linked,
+      }
+      .and_then(|()| fs::remove_file(&tmp))
+    }
+  });
+
+  if let Err(err) = written {
+    let _ = fs::remove_file(&tmp);
+    return Err(with_path(err).into());
+  }
+
+  Ok(())
+}
+
+/// Create a new file at `path` with `bytes`, failing when it exists, for file systems without hard links.
+///
+/// Unlike a hard link the file can be seen while it is written, a failed write removes it again.
+fn write_new(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+  let written = file.write_all(bytes).and_then(|()| file.sync_all());
+
+  if written.is_err() {
+    let _ = fs::remove_file(path);
+  }
+  written
+}
+
+/// Read the `Content-Type` header of a Tinify result.
+pub(crate) fn content_type(headers: &HeaderMap) -> Option<String> {
+  headers
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .map(str::to_string)
+}
+
+/// Copy `headers` with the `Location` replaced, it links to the result without needing the key.
+pub(crate) fn redact_location(headers: &HeaderMap) -> HeaderMap {
+  let mut headers = headers.clone();
+  if headers.contains_key(LOCATION) {
+    headers.insert(LOCATION, HeaderValue::from_static("<redacted>"));
+  }
+
+  headers
+}
+
+/// Read the `Image-Width` and `Image-Height` headers of a Tinify result.
+pub(crate) fn image_dimensions(headers: &HeaderMap) -> Option<(u32, u32)> {
+  let parse = |name: &str| {
+    headers
+      .get(name)
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.trim().parse::<u32>().ok())
+  };
+
+  Some((parse("image-width")?, parse("image-height")?))
+}
+
+/// Parse a numeric header strictly, `None` when it is missing and a `ParseIntError` when it isn't a number.
+pub(crate) fn parse_header(
+  headers: &HeaderMap,
+  name: &str,
+) -> Result<Option<u32>, TinifyError> {
+  headers
+    .get(name)
+    .map(|value| Ok(value.to_str()?.trim().parse::<u32>()?))
+    .transpose()
+}
+
+/// Read the file name suggested by the `Content-Disposition` header of a download, preferring `filename*` over `filename`.
+///
+/// Only a plain file name is returned. A name with a directory, like `../../etc/passwd`, and `.` or `..` are rejected, so the name can't escape the directory it is saved to.
+pub(crate) fn suggested_file_name(headers: &HeaderMap) -> Option<String> {
+  let value = headers.get(CONTENT_DISPOSITION)?.to_str().ok()?;
+  let mut plain = None;
+  let mut extended = None;
+
+  for param in value.split(';').skip(1) {
+    if let Some((name, value)) = param.split_once('=') {
+      let value = value.trim();
+      match name.trim().to_ascii_lowercase().as_str() {
+        "filename*" => extended = decode_ext_value(value),
+        "filename" => plain = Some(value.trim_matches('"').to_string()),
+        _ => {}
+      }
+    }
+  }
+  let name = extended.or(plain)?;
+  let valid = !matches!(name.as_str(), "" | "." | "..")
+    && !name.contains(['/', '\\', '\0']);
+
+  valid.then_some(name)
+}
+
+/// Decode an extended parameter value like `UTF-8''%e2%82%ac%20rates.png`, only UTF-8 is supported.
+fn decode_ext_value(value: &str) -> Option<String> {
+  let mut parts = value.splitn(3, '\'');
+  let charset = parts.next()?;
+  let _language = parts.next()?;
+  let encoded = parts.next()?;
+
+  if !charset.eq_ignore_ascii_case("utf-8") {
+    return None;
+  }
+
+  let mut bytes = Vec::with_capacity(encoded.len());
+  let mut rest = encoded.as_bytes();
+  while let Some((&byte, tail)) = rest.split_first() {
+    if byte == b'%' {
+      let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+      bytes.push(u8::from_str_radix(hex, 16).ok()?);
+      rest = &tail[2..];
+    } else {
+      bytes.push(byte);
+      rest = tail;
+    }
+  }
+
+  String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::transform::Background;
+  use reqwest::header::HeaderValue;
+  use std::env;
+
+  #[test]
+  fn test_operations_order() -> Result<(), serde_json::Error> {
+    let operations = Operations {
+      transform: Some(Transform {
+        background: Background::White,
+      }),
+      resize: Some(Resize {
+        method: Method::Fit,
+        width: Some(400),
+        height: Some(200),
+      }),
+      convert: Some(Convert {
+        r#type: vec![Type::Jpeg],
+      }),
+      preserve: Some(Preserve {
+        metadata: vec![Metadata::Copyright],
+      }),
+      ..Operations::default()
+    };
+
+    assert_eq!(
+      serde_json::to_string(&operations)?,
+      concat!(
+        r#"{"convert":{"type":["image/jpeg"]},"#,
+        r#""resize":{"method":"fit","width":400,"height":200},"#,
+        r#""transform":{"background":"white"},"#,
+        r#""preserve":["copyright"]}"#,
+      )
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_operations_serialize_matrix() -> Result<(), serde_json::Error> {
+    fn has_null(value: &serde_json::Value) -> bool {
+      match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Array(values) => values.iter().any(has_null),
+        serde_json::Value::Object(map) => map.values().any(has_null),
+        _ => false,
+      }
+    }
+
+    let resizes = [
+      Resize {
+        method: Method::Scale,
+        width: Some(400),
+        height: None,
+      },
+      Resize {
+        method: Method::Scale,
+        width: None,
+        height: Some(200),
+      },
+      Resize {
+        method: Method::Thumb,
+        width: Some(400),
+        height: Some(200),
+      },
+    ];
+
+    for resize in resizes {
+      for mask in 0..16u8 {
+        let operations = Operations {
+          convert: (mask & 1 != 0).then(|| Convert {
+            r#type: vec![Type::Webp],
+          }),
+          resize: (mask & 2 != 0).then(|| resize.clone()),
+          transform: (mask & 4 != 0).then_some(Transform {
+            background: Background::White,
+          }),
+          preserve: (mask & 8 != 0).then(|| Preserve {
+            metadata: vec![Metadata::Creation],
+          }),
+          ..Operations::default()
+        };
+        let body = serde_json::to_string(&operations)?;
+        let value: serde_json::Value = serde_json::from_str(&body)?;
+        let mut keys: Vec<_> = value
+          .as_object()
+          .map(|map| map.keys().cloned().collect())
+          .unwrap_or_default();
+        let mut expected: Vec<_> =
+          ["convert", "resize", "transform", "preserve"]
+            .into_iter()
+            .enumerate()
+            .filter(|(bit, _)| mask & (1 << bit) != 0)
+            .map(|(_, key)| key.to_string())
+            .collect();
+        keys.sort();
+        expected.sort();
+
+        assert!(!has_null(&value), "{}", body);
+        assert_eq!(keys, expected, "{}", body);
+        assert_eq!(serde_json::from_str::<Operations>(&body)?, operations);
+      }
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_raw_operations() -> Result<(), TinifyError> {
+    let mut operations = Operations {
+      convert: Some(Convert {
+        r#type: vec![Type::Webp],
+      }),
+      ..Operations::default()
+    };
+    operations.set_raw("watermark", serde_json::json!({"text": "draft"}))?;
+
+    assert!(!operations.is_empty());
+    assert_eq!(
+      serde_json::to_string(&operations)?,
+      r#"{"convert":{"type":["image/webp"]},"watermark":{"text":"draft"}}"#
+    );
+    assert_eq!(
+      operations.to_string(),
+      r#"convert(webp) + watermark({"text":"draft"})"#
+    );
+
+    for (name, value) in [
+      ("resize", serde_json::json!({"method": "scale"})),
+      ("store", serde_json::json!({"service": "s3"})),
+      ("watermark", serde_json::Value::Null),
+    ] {
+      let request = operations.set_raw(name, value);
+      assert!(matches!(
+        request,
+        Err(TinifyError::ClientError { ref upstream }) if upstream.error == "Operation"
+      ));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_store_operations() -> Result<(), TinifyError> {
+    let operations = Operations {
+      convert: Some(Convert {
+        r#type: vec![Type::Webp],
+      }),
+      ..Operations::default()
+    };
+    let store = Store::gcs("ya29.token", "bucket/optimized.webp");
+    let body = StoreOperations {
+      operations: &operations,
+      store: &store,
+    };
+
+    assert_eq!(
+      serde_json::to_string(&body)?,
+      r#"{"convert":{"type":["image/webp"]},"store":{"service":"gcs","gcp_access_token":"ya29.token","path":"bucket/optimized.webp"}}"#
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_operations_clone_eq() {
+    let resize = Resize {
+      method: Method::Cover,
+      width: Some(150),
+      height: Some(100),
+    };
+    let operations = Operations {
+      resize: Some(resize.clone()),
+      transform: Some(Transform {
+        background: Background::Black,
+      }),
+      ..Operations::default()
+    };
+    let mut other = operations.clone();
+
+    assert_eq!(operations, other);
+    assert_eq!(operations.resize, Some(resize));
+
+    other.resize = None;
+    assert_ne!(operations, other);
+  }
+
+  #[test]
+  fn test_resize_with_preserve() -> Result<(), serde_json::Error> {
+    let operations = Operations {
+      resize: Some(Resize {
+        method: Method::Scale,
+        width: Some(400),
+        height: None,
+      }),
+      preserve: Some(Preserve {
+        metadata: vec![Metadata::Copyright, Metadata::Creation],
+      }),
+      ..Operations::default()
+    };
+
+    assert_eq!(
+      serde_json::to_string(&operations)?,
+      concat!(
+        r#"{"resize":{"method":"scale","width":400},"#,
+        r#""preserve":["copyright","creation"]}"#,
+      )
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_check_background() {
+    let mut operations = Operations {
+      convert: Some(Convert {
+        r#type: vec![Type::Jpeg],
+      }),
+      ..Operations::default()
+    };
+
+    assert!(operations.check_background(false).is_ok());
+    assert!(matches!(
+      operations.check_background(true),
+      Err(TinifyError::ClientError { ref upstream })
+        if upstream.error == "Transparency"
+    ));
+
+    operations.transform = Some(Transform {
+      background: Background::White,
+    });
+    assert!(operations.check_background(true).is_ok());
+
+    operations.transform = None;
+    operations.convert = Some(Convert {
+      r#type: vec![Type::Jpeg, Type::Png],
+    });
+    assert!(operations.check_background(true).is_ok());
+  }
+
+  #[test]
+  fn test_check_upscale() {
+    let resize = |method, width, height| Operations {
+      resize: Some(Resize {
+        method,
+        width,
+        height,
+      }),
+      ..Operations::default()
+    };
+    let scale = |width, height| resize(Method::Scale, width, height);
+
+    assert!(scale(Some(400), None)
+      .check_upscale(Some((510, 1020)), None)
+      .is_ok());
+    assert!(scale(Some(2000), None).check_upscale(None, None).is_ok());
+    assert!(matches!(
+      scale(None, Some(2000)).check_upscale(Some((510, 1020)), None),
+      Err(TinifyError::ClientError { ref upstream })
+        if upstream.error == "Upscale"
+    ));
+
+    let fit = |width, height| resize(Method::Fit, Some(width), Some(height));
+    assert!(fit(1000, 100).check_upscale(Some((500, 500)), None).is_ok());
+    assert!(fit(1000, 1000)
+      .check_upscale(Some((500, 500)), None)
+      .is_err());
+    for method in [Method::Cover, Method::Thumb] {
+      assert!(resize(method, Some(1000), Some(1000))
+        .check_upscale(Some((500, 500)), None)
+        .is_ok());
+    }
+
+    // Stored as 1020x510, displayed as 510x1020.
+    assert!(scale(None, Some(800))
+      .check_upscale(Some((1020, 510)), Some(6))
+      .is_ok());
+    assert!(scale(None, Some(800))
+      .check_upscale(Some((1020, 510)), Some(1))
+      .is_err());
+  }
+
+  #[test]
+  fn test_check_resize() {
+    let resize = |resize| Operations {
+      resize: Some(resize),
+      ..Operations::default()
+    };
+
+    assert!(matches!(
+      resize(Resize::default()).check_resize(),
+      Err(TinifyError::ClientError { ref upstream })
+        if upstream.error == "Resize"
+    ));
+    assert!(resize(Resize {
+      width: Some(400),
+      height: Some(200),
+      ..Default::default()
+    })
+    .check_resize()
+    .is_ok());
+    assert!(resize(Resize {
+      method: Method::Scale,
+      height: Some(200),
+      ..Default::default()
+    })
+    .check_resize()
+    .is_ok());
+    assert!(resize(Resize {
+      method: Method::Scale,
+      width: Some(400),
+      height: Some(200),
+    })
+    .check_resize()
+    .is_err());
+    assert!(resize(Resize {
+      method: Method::Cover,
+      width: Some(400),
+      ..Default::default()
+    })
+    .check_resize()
+    .is_err());
+    assert!(resize(Resize {
+      method: Method::Scale,
+      width: Some(0),
+      height: None,
+    })
+    .check_resize()
+    .is_err());
+  }
+
+  #[test]
+  fn test_convert_default() -> Result<(), serde_json::Error> {
+    assert_eq!(
+      serde_json::to_string(&Convert::default())?,
+      r#"{"type":["*/*"]}"#
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_operations_summary() {
+    let mut operations = Operations::default();
+    assert_eq!(operations.to_string(), "none");
+
+    operations.convert = Some(Convert {
+      r#type: vec![Type::Webp, Type::Png],
+    });
+    operations.resize = Some(Resize {
+      method: Method::Fit,
+      width: Some(400),
+      height: Some(200),
+    });
+    assert_eq!(
+      operations.to_string(),
+      "resize(fit 400x200) + convert(webp|png)"
+    );
+
+    operations.transform = Some(Transform {
+      background: Background::rgb(128, 0, 32),
+    });
+    operations.preserve = Some(Preserve {
+      metadata: vec![Metadata::Copyright, Metadata::Location],
+    });
+    assert_eq!(
+      operations.to_string(),
+      concat!(
+        "resize(fit 400x200) + convert(webp|png) + ",
+        "transform(background #800020) + preserve(copyright, location)",
+      )
+    );
+  }
+
+  #[test]
+  fn test_classify_status() {
+    let body = br#"{"error":"Error","message":"Failed."}"#;
+    let classify = |status, expected: StatusCode| {
+      classify_status(status, &[expected], None, body, DEFAULT_ERROR_BODY_LIMIT)
+    };
+
+    assert!(classify(StatusCode::CREATED, StatusCode::CREATED).is_ok());
+    assert!(classify(StatusCode::OK, StatusCode::OK).is_ok());
+    for (status, expected) in [
+      (StatusCode::OK, StatusCode::CREATED),
+      (StatusCode::CREATED, StatusCode::OK),
+    ] {
+      assert!(matches!(
+        classify(status, expected),
+        Err(TinifyError::ServerError { upstream, .. }) if upstream.error == "Status"
+      ));
+    }
+    for status in [
+      StatusCode::BAD_REQUEST,
+      StatusCode::UNAUTHORIZED,
+      StatusCode::PAYMENT_REQUIRED,
+      StatusCode::NOT_FOUND,
+      StatusCode::UNSUPPORTED_MEDIA_TYPE,
+    ] {
+      assert!(matches!(
+        classify(status, StatusCode::CREATED),
+        Err(TinifyError::ClientError { upstream }) if upstream.message == "Failed."
+      ));
+    }
+    for status in [
+      StatusCode::FOUND,
+      StatusCode::INTERNAL_SERVER_ERROR,
+      StatusCode::SERVICE_UNAVAILABLE,
+    ] {
+      assert!(matches!(
+        classify(status, StatusCode::CREATED),
+        Err(TinifyError::ServerError { upstream, .. }) if upstream.error == "Error"
+      ));
+    }
+
+    let limited = classify_status(
+      StatusCode::TOO_MANY_REQUESTS,
+      &[StatusCode::OK],
+      Some(Duration::from_secs(30)),
+      body,
+      DEFAULT_ERROR_BODY_LIMIT,
+    )
+    .unwrap_err();
+    assert!(matches!(limited, TinifyError::TooManyRequests { .. }));
+    assert_eq!(limited.retry_after(), Some(Duration::from_secs(30)));
+
+    let accepted = [StatusCode::OK, StatusCode::CREATED, StatusCode::ACCEPTED];
+    for status in accepted {
+      assert!(classify_status(status, &accepted, None, b"", 0).is_ok());
+    }
+    assert!(matches!(
+      classify_status(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        &accepted,
+        None,
+        b"<html>Bad Gateway</html>",
+        DEFAULT_ERROR_BODY_LIMIT,
+      ),
+      Err(TinifyError::ServerError { upstream, .. })
+        if upstream.error == "Internal Server Error"
+          && upstream.message == "<html>Bad Gateway</html>"
+    ));
+  }
+
+  #[test]
+  fn test_parse_header() -> Result<(), TinifyError> {
+    let mut headers = HeaderMap::new();
+    headers.insert("image-width", HeaderValue::from_static(" 400 "));
+    headers.insert("compression-count", HeaderValue::from_static("12abc"));
+
+    assert_eq!(parse_header(&headers, "image-width")?, Some(400));
+    assert_eq!(parse_header(&headers, "image-height")?, None);
+    assert!(matches!(
+      parse_header(&headers, "compression-count"),
+      Err(TinifyError::ParseIntError(_))
+    ));
+    assert_eq!(image_dimensions(&headers), None);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_retry_after_seconds() {
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+    assert_eq!(retry_after(&HeaderMap::new()), None);
+  }
+
+  #[test]
+  fn test_retry_after_http_date() {
+    let now =
+      httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+
+    assert_eq!(
+      parse_retry_after("Wed, 21 Oct 2015 07:29:30 GMT", now),
+      Some(Duration::from_secs(90))
+    );
+    assert_eq!(
+      parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+      Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+  }
+
+  #[test]
+  fn test_parse_upstream() -> Result<(), TinifyError> {
+    let body =
+      br#"{"error":"Unauthorized","message":"Credentials are invalid."}"#;
+    let upstream = parse_upstream(body, DEFAULT_ERROR_BODY_LIMIT)?;
+    assert_eq!(upstream.error, "Unauthorized");
+
+    let upstream = parse_upstream(&[b'x'; 11], 10)?;
+    assert_eq!(upstream.error, "Truncated");
+    assert!(upstream.message.starts_with("xxxxxxxxxx..."));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_path_with_suffix() {
+    assert_eq!(
+      path_with_suffix(Path::new("out/optimized.png"), ".json"),
+      Path::new("out/optimized.png.json")
+    );
+    assert_eq!(
+      path_with_suffix(Path::new("./assets/logo.png"), ".orig"),
+      Path::new("./assets/logo.png.orig")
+    );
+  }
+
+  #[test]
+  fn test_write_atomic() -> Result<(), TinifyError> {
+    let dir = env::temp_dir().join(format!("tinify-write-{}", process::id()));
+    let path = dir.join("optimized.png");
+    fs::create_dir_all(&dir)?;
+
+    write_atomic(&path, b"first")?;
+    write_atomic(&path, b"second")?;
+    assert_eq!(fs::read(&path)?, b"second");
+
+    let target = dir.join("target");
+    fs::create_dir_all(target.join("not_empty"))?;
+    let err = write_atomic(&target, b"third").unwrap_err();
+    assert!(err.to_string().contains("target"));
+    assert_eq!(fs::read_dir(&dir)?.count(), 2);
+
+    let missing = dir.join("missing").join("optimized.png");
+    assert!(write_atomic(&missing, b"fourth").is_err());
+
+    create_parent_dirs(&missing)?;
+    write_atomic(&missing, b"fourth")?;
+    assert_eq!(fs::read(&missing)?, b"fourth");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_read_file_progress() -> Result<(), TinifyError> {
+    let dir = env::temp_dir().join(format!("tinify-read-{}", process::id()));
+    let path = dir.join("large.png");
+    let size = READ_CHUNK * 2 + 512;
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, vec![7; size as usize])?;
+
+    let mut reports = Vec::new();
+    let buffer = read_file(&path, |read, total| reports.push((read, total)))?;
+
+    assert_eq!(buffer.len() as u64, size);
+    assert_eq!(
+      reports,
+      [
+        (0, size),
+        (READ_CHUNK, size),
+        (READ_CHUNK * 2, size),
+        (size, size)
+      ]
+    );
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_output() -> Result<(), TinifyError> {
+    let dir =
+      env::temp_dir().join(format!("tinify-write-output-{}", process::id()));
+    let path = dir.join("images").join("optimized.png");
+    let config = Config {
+      create_dirs: true,
+      no_overwrite: true,
+      ..Config::default()
+    };
+
+    write_output(&config, &path, b"first")?;
+    assert_eq!(fs::read(&path)?, b"first");
+
+    let err = write_output(&config, &path, b"second").unwrap_err();
+    assert!(matches!(
+      err,
+      TinifyError::IOError(ref err) if err.kind() == io::ErrorKind::AlreadyExists
+    ));
+    assert_eq!(fs::read(&path)?, b"first");
+    assert_eq!(fs::read_dir(path.parent().unwrap())?.count(), 1);
+
+    let config = Config {
+      no_overwrite: false,
+      ..config
+    };
+    write_output(&config, &path, b"third")?;
+    assert_eq!(fs::read(&path)?, b"third");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_write_new() -> Result<(), TinifyError> {
+    let dir =
+      env::temp_dir().join(format!("tinify-write-new-{}", process::id()));
+    let path = dir.join("optimized.png");
+    fs::create_dir_all(&dir)?;
+
+    write_new(&path, b"first")?;
+    assert_eq!(
+      write_new(&path, b"second").unwrap_err().kind(),
+      io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(fs::read(&path)?, b"first");
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_compression_count() {
+    let count = CompressionCount::default();
+    let shared = count.clone();
+    let mut headers = HeaderMap::new();
+
+    count.update(&headers);
+    assert_eq!(shared.get(), None);
+
+    headers.insert("Compression-Count", HeaderValue::from_static("42"));
+    count.update(&headers);
+    assert_eq!(shared.get(), Some(42));
+
+    headers.insert("Compression-Count", HeaderValue::from_static("n/a"));
+    count.update(&headers);
+    assert_eq!(shared.get(), Some(42));
+  }
+
+  #[test]
+  fn test_image_dimensions() {
+    let mut headers = HeaderMap::new();
+
+    assert_eq!(image_dimensions(&headers), None);
+
+    headers.insert("Image-Width", HeaderValue::from_static("200"));
+    assert_eq!(image_dimensions(&headers), None);
+
+    headers.insert("Image-Height", HeaderValue::from_static("100"));
+    assert_eq!(image_dimensions(&headers), Some((200, 100)));
+
+    headers.insert("Image-Height", HeaderValue::from_static("tall"));
+    assert_eq!(image_dimensions(&headers), None);
+  }
+
+  #[test]
+  fn test_suggested_file_name() {
+    let name = |value: &'static str| {
+      let mut headers = HeaderMap::new();
+      headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static(value));
+      suggested_file_name(&headers)
+    };
+
+    assert_eq!(suggested_file_name(&HeaderMap::new()), None);
+    assert_eq!(name("inline"), None);
+    assert_eq!(
+      name(r#"attachment; filename="panda.png""#).as_deref(),
+      Some("panda.png")
+    );
+    assert_eq!(
+      name("attachment; FILENAME=panda.webp").as_deref(),
+      Some("panda.webp")
+    );
+    assert_eq!(
+      name(r#"attachment; filename="rates.png"; filename*=UTF-8''%e2%82%ac%20rates.png"#)
+        .as_deref(),
+      Some("\u{20ac} rates.png")
+    );
+    assert_eq!(name(r#"attachment; filename="../../etc/passwd""#), None);
+    assert_eq!(name(r#"attachment; filename="..""#), None);
+    assert_eq!(name(r#"attachment; filename="C:\\panda.png""#), None);
+    assert_eq!(
+      name("attachment; filename*=UTF-8''%2e%2e%2fpanda.png"),
+      None
+    );
+  }
 }
@@ -0,0 +1,219 @@
+use std::sync::Arc;
+#[cfg(not(feature = "async"))]
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
+#[cfg(feature = "async")]
+use tokio::sync::Notify;
+
+/// The limiter of the process, created by the first `set_global_concurrency` and shared by every client after it.
+static GLOBAL: OnceLock<Limiter> = OnceLock::new();
+
+/// Get the limiter of the process and set it to `limit` requests in flight, at least one.
+///
+/// There is only one, another limit resizes it for every client holding it.
+pub(crate) fn global(limit: usize) -> Limiter {
+  let limit = limit.max(1);
+  let limiter = GLOBAL.get_or_init(|| Limiter::new(limit));
+  limiter.resize(limit);
+
+  limiter.clone()
+}
+
+/// The budget of a limiter and how much of it is used.
+#[derive(Debug)]
+struct Counts {
+  limit: usize,
+  in_flight: usize,
+}
+
+/// Caps the number of requests in flight, across every client holding a clone of it.
+#[derive(Clone, Debug)]
+pub(crate) struct Limiter {
+  #[cfg(not(feature = "async"))]
+  state: Arc<(Mutex<Counts>, Condvar)>,
+  #[cfg(feature = "async")]
+  state: Arc<(Mutex<Counts>, Notify)>,
+}
+
+impl Limiter {
+  fn counts(&self) -> MutexGuard<'_, Counts> {
+    self.state.0.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// Count a request in flight if the budget allows it.
+  #[cfg(any(test, feature = "async"))]
+  fn try_acquire(&self) -> Option<Permit<'_>> {
+    let mut counts = self.counts();
+    if counts.in_flight >= counts.limit {
+      return None;
+    }
+    counts.in_flight += 1;
+
+    Some(Permit(self))
+  }
+}
+
+#[cfg(not(feature = "async"))]
+impl Limiter {
+  fn new(limit: usize) -> Self {
+    let counts = Counts {
+      limit,
+      in_flight: 0,
+    };
+
+    Self {
+      state: Arc::new((Mutex::new(counts), Condvar::new())),
+    }
+  }
+
+  /// Change the budget, requests already in flight keep their permit.
+  fn resize(&self, limit: usize) {
+    self.counts().limit = limit;
+    self.state.1.notify_all();
+  }
+
+  /// Block until a request may be sent, it stays in flight until the permit is dropped.
+  pub(crate) fn acquire(&self) -> Permit<'_> {
+    let (counts, available) = &*self.state;
+    let mut counts = counts.lock().unwrap_or_else(PoisonError::into_inner);
+
+    while counts.in_flight >= counts.limit {
+      counts = available
+        .wait(counts)
+        .unwrap_or_else(PoisonError::into_inner);
+    }
+    counts.in_flight += 1;
+
+    Permit(self)
+  }
+
+  fn release(&self) {
+    self.counts().in_flight -= 1;
+    self.state.1.notify_one();
+  }
+}
+
+#[cfg(feature = "async")]
+impl Limiter {
+  fn new(limit: usize) -> Self {
+    let counts = Counts {
+      limit,
+      in_flight: 0,
+    };
+
+    Self {
+      state: Arc::new((Mutex::new(counts), Notify::new())),
+    }
+  }
+
+  /// Change the budget, requests already in flight keep their permit.
+  fn resize(&self, limit: usize) {
+    self.counts().limit = limit;
+    self.state.1.notify_waiters();
+  }
+
+  /// Wait until a request may be sent, it stays in flight until the permit is dropped.
+  pub(crate) async fn acquire(&self) -> Permit<'_> {
+    loop {
+      // Registered before the check, so a release in between still wakes it.
+      let available = self.state.1.notified();
+      if let Some(permit) = self.try_acquire() {
+        return permit;
+      }
+      available.await;
+    }
+  }
+
+  fn release(&self) {
+    self.counts().in_flight -= 1;
+    self.state.1.notify_waiters();
+  }
+}
+
+/// A request in flight, counted until it is dropped.
+pub(crate) struct Permit<'a>(&'a Limiter);
+
+impl Drop for Permit<'_> {
+  fn drop(&mut self) {
+    self.0.release();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::AtomicUsize;
+  use std::sync::atomic::Ordering;
+  #[cfg(not(feature = "async"))]
+  use std::thread;
+  use std::time::Duration;
+
+  #[test]
+  fn test_resize() {
+    let limiter = Limiter::new(1);
+    let first = limiter.try_acquire();
+
+    assert!(first.is_some());
+    assert!(limiter.try_acquire().is_none());
+    limiter.resize(2);
+    let second = limiter.try_acquire();
+    assert!(second.is_some());
+    limiter.resize(1);
+    drop(first);
+    assert!(limiter.try_acquire().is_none());
+    drop(second);
+    assert!(limiter.try_acquire().is_some());
+  }
+
+  #[cfg(not(feature = "async"))]
+  #[test]
+  fn test_limit_in_flight() {
+    let limiter = Limiter::new(2);
+    let in_flight = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+      for _ in 0..8 {
+        scope.spawn(|| {
+          let _permit = limiter.acquire();
+          let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+          most.fetch_max(count, Ordering::SeqCst);
+          thread::sleep(Duration::from_millis(10));
+          in_flight.fetch_sub(1, Ordering::SeqCst);
+        });
+      }
+    });
+
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_limit_in_flight() {
+    let limiter = Limiter::new(2);
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..8)
+      .map(|_| {
+        let (limiter, in_flight, most) =
+          (limiter.clone(), in_flight.clone(), most.clone());
+        tokio::spawn(async move {
+          let _permit = limiter.acquire().await;
+          let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+          most.fetch_max(count, Ordering::SeqCst);
+          tokio::time::sleep(Duration::from_millis(10)).await;
+          in_flight.fetch_sub(1, Ordering::SeqCst);
+        })
+      })
+      .collect();
+
+    for task in tasks {
+      task.await.unwrap();
+    }
+
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+  }
+}
@@ -0,0 +1,290 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use reqwest::header::HeaderMap;
+use reqwest::header::LOCATION;
+use std::fmt;
+use url::Url;
+
+/// The url of a compressed image, from the `Location` of a shrink request.
+///
+/// It can only be built from a response of the API, so an arbitrary string can't be passed where a result url is expected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResultUrl(Url);
+
+impl ResultUrl {
+  /// Resolve a `Location`, which is either an absolute url, possibly on another host, or a path relative to the `endpoint` of the request.
+  ///
+  /// Only `https` urls with a host are accepted, and urls on the endpoint itself, so a local `http` endpoint works in tests.
+  pub(crate) fn parse(
+    location: &str,
+    endpoint: &Url,
+  ) -> Result<Self, TinifyError> {
+    let url = endpoint.join(location.trim())?;
+    let trusted = url.scheme() == "https" || url.origin() == endpoint.origin();
+
+    if !trusted || url.host_str().is_none() {
+      let upstream = Upstream {
+        error: "Location".to_string(),
+        message: format!("Invalid location of the compressed image `{}`.", url),
+      };
+      return Err(TinifyError::ServerError {
+        upstream,
+        status: None,
+      });
+    }
+
+    Ok(Self(url))
+  }
+
+  /// Get the url as a [`Url`].
+  pub fn as_url(&self) -> &Url {
+    &self.0
+  }
+}
+
+/// Parse the url of an image for Tinify to download, before sending it.
+///
+/// A malformed url fails with a `UrlParseError`, and one that isn't `http` or `https` with a host, like `file:///etc/passwd`, with a `ClientError` whose error is `Url`.
+pub(crate) fn source_url(url: &str) -> Result<Url, TinifyError> {
+  let url = Url::parse(url.trim())?;
+
+  if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+    let upstream = Upstream {
+      error: "Url".to_string(),
+      message: format!(
+        "Unsupported url `{}`, only `http` and `https` images can be compressed from an url.",
+        url
+      ),
+    };
+    return Err(TinifyError::ClientError { upstream });
+  }
+
+  Ok(url)
+}
+
+/// Parse the endpoint set with `Tinify::set_endpoint`.
+///
+/// A malformed url fails with a `UrlParseError`, and one that isn't `http` or `https` with a host with a `ClientError` whose error is `Endpoint`.
+pub(crate) fn endpoint_url(endpoint: &str) -> Result<Url, TinifyError> {
+  let url = Url::parse(endpoint.trim())?;
+
+  if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+    let upstream = Upstream {
+      error: "Endpoint".to_string(),
+      message: format!(
+        "Unsupported endpoint `{}`, expected an `http` or `https` url.",
+        url
+      ),
+    };
+    return Err(TinifyError::ClientError { upstream });
+  }
+
+  Ok(url)
+}
+
+/// Read the url an image was stored at by `Source::store` from the `Location` of the response.
+///
+/// A missing or malformed `Location` fails with a `ServerError` whose error is `Location`, the image may still have been stored.
+pub(crate) fn stored_url(headers: &HeaderMap) -> Result<Url, TinifyError> {
+  let location = headers
+    .get(LOCATION)
+    .and_then(|location| location.to_str().ok())
+    .and_then(|location| Url::parse(location.trim()).ok());
+
+  location.ok_or_else(|| {
+    let upstream = Upstream {
+      error: "Location".to_string(),
+      message: "The location of the stored image is missing or invalid."
+        .to_string(),
+    };
+    TinifyError::ServerError {
+      upstream,
+      status: None,
+    }
+  })
+}
+
+/// Check the basic auth credentials of an origin before they are sent, without putting them in the error.
+///
+/// Basic auth joins them as `user:password`, so the user can't be empty or contain a `:`, and neither can contain control characters like a line break that would corrupt the header.
+pub(crate) fn check_credentials(
+  user: &str,
+  password: &str,
+) -> Result<(), TinifyError> {
+  let message = if user.is_empty() {
+    "The user of the origin is empty."
+  } else if user.contains(':') {
+    "The user of the origin can't contain a `:`."
+  } else if user.chars().chain(password.chars()).any(char::is_control) {
+    "The credentials of the origin can't contain control characters."
+  } else {
+    return Ok(());
+  };
+
+  let upstream = Upstream {
+    error: "Credentials".to_string(),
+    message: message.to_string(),
+  };
+  Err(TinifyError::ClientError { upstream })
+}
+
+impl fmt::Display for ResultUrl {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.0.as_str())
+  }
+}
+
+impl AsRef<str> for ResultUrl {
+  fn as_ref(&self) -> &str {
+    self.0.as_str()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::API_ENDPOINT;
+  use assert_matches::assert_matches;
+  use reqwest::header::HeaderValue;
+
+  #[test]
+  fn test_check_credentials() {
+    assert!(check_credentials("deploy", "s3cr:t").is_ok());
+    assert!(check_credentials("deploy", "").is_ok());
+
+    for (user, password) in
+      [("", "pass"), ("de:ploy", "pass"), ("deploy", "pa\r\nss")]
+    {
+      assert_matches!(
+        check_credentials(user, password),
+        Err(TinifyError::ClientError { upstream })
+          if upstream.error == "Credentials" && !upstream.message.contains("pass")
+      );
+    }
+  }
+
+  #[test]
+  fn test_stored_url() -> Result<(), TinifyError> {
+    let mut headers = HeaderMap::new();
+    assert_matches!(
+      stored_url(&headers),
+      Err(TinifyError::ServerError { upstream, .. }) if upstream.error == "Location"
+    );
+
+    headers.insert(
+      LOCATION,
+      HeaderValue::from_static(
+        "https://s3-us-west-1.amazonaws.com/bucket/optimized.jpg",
+      ),
+    );
+    assert_eq!(
+      stored_url(&headers)?.as_str(),
+      "https://s3-us-west-1.amazonaws.com/bucket/optimized.jpg"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_resolve_relative_location() -> Result<(), TinifyError> {
+    let endpoint = Url::parse(API_ENDPOINT)?;
+    let url = ResultUrl::parse("/output/2xnsp7jn34e5.png", &endpoint)?;
+
+    assert_eq!(
+      url.as_ref(),
+      "https://api.tinify.com/output/2xnsp7jn34e5.png"
+    );
+
+    let endpoint = Url::parse("http://127.0.0.1:8080")?;
+    let url = ResultUrl::parse("/output/2xnsp7jn34e5.png", &endpoint)?;
+
+    assert_eq!(
+      url.as_ref(),
+      "http://127.0.0.1:8080/output/2xnsp7jn34e5.png"
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_resolve_absolute_location() -> Result<(), TinifyError> {
+    let endpoint = Url::parse(API_ENDPOINT)?;
+    let location = "https://cdn.example.com/output/2xnsp7jn34e5.png";
+
+    assert_eq!(ResultUrl::parse(location, &endpoint)?.to_string(), location);
+    assert_eq!(
+      ResultUrl::parse("//cdn.example.com/output/2xnsp7jn34e5.png", &endpoint)?
+        .to_string(),
+      location
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_invalid_location() -> Result<(), TinifyError> {
+    let endpoint = Url::parse("http://127.0.0.1:8080")?;
+
+    for location in [
+      "http://api.tinify.com/output/2xnsp7jn34e5.png",
+      "http://127.0.0.1:9090/output/2xnsp7jn34e5.png",
+      "file:///etc/passwd",
+    ] {
+      assert_matches!(
+        ResultUrl::parse(location, &endpoint),
+        Err(TinifyError::ServerError { .. })
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_endpoint_url() -> Result<(), TinifyError> {
+    assert_eq!(
+      endpoint_url(" http://127.0.0.1:8080 ")?.as_str(),
+      "http://127.0.0.1:8080/"
+    );
+
+    for endpoint in ["ftp://127.0.0.1", "unix:/var/run/tinify.sock"] {
+      assert_matches!(
+        endpoint_url(endpoint),
+        Err(TinifyError::ClientError { upstream }) if upstream.error == "Endpoint"
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_source_url() -> Result<(), TinifyError> {
+    assert_eq!(
+      source_url(" https://tinypng.com/images/panda-happy.png ")?.as_str(),
+      "https://tinypng.com/images/panda-happy.png"
+    );
+    assert!(source_url("http://example.com/image.png").is_ok());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_malformed_source_url() {
+    for url in ["panda-happy.png", "https://", "http://[::1"] {
+      assert_matches!(source_url(url), Err(TinifyError::UrlParseError(_)));
+    }
+  }
+
+  #[test]
+  fn test_unsupported_source_url() {
+    for url in [
+      "file:///etc/passwd",
+      "ftp://example.com/image.png",
+      "data:image/png;base64,",
+    ] {
+      assert_matches!(
+        source_url(url),
+        Err(TinifyError::ClientError { ref upstream }) if upstream.error == "Url"
+      );
+    }
+  }
+}
@@ -0,0 +1,238 @@
+//! A local HTTP server for tests, to drive the clients through `Tinify::set_endpoint` without calling the API.
+
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long the server waits for the next request before it gives up, so a test failing early never hangs.
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A response of the mock server.
+pub(crate) struct Reply {
+  status: &'static str,
+  headers: Vec<(&'static str, String)>,
+  body: Vec<u8>,
+  delay: Duration,
+}
+
+impl Reply {
+  pub(crate) fn new(status: &'static str) -> Self {
+    Self {
+      status,
+      headers: Vec::new(),
+      body: Vec::new(),
+      delay: Duration::ZERO,
+    }
+  }
+
+  pub(crate) fn header(mut self, name: &'static str, value: &str) -> Self {
+    self.headers.push((name, value.to_string()));
+    self
+  }
+
+  pub(crate) fn body(mut self, body: &[u8]) -> Self {
+    self.body = body.to_vec();
+    self
+  }
+
+  /// Answer only `delay` after the request was read, to run into a timeout of the client.
+  pub(crate) fn delay(mut self, delay: Duration) -> Self {
+    self.delay = delay;
+    self
+  }
+}
+
+/// Answer one request with every reply in turn, each on its own connection, and return the url of the server.
+///
+/// The server thread returns the requests it received, head and body, and stops after the last reply or once no request came for a while.
+pub(crate) fn serve(
+  replies: Vec<Reply>,
+) -> (String, thread::JoinHandle<Vec<String>>) {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  listener.set_nonblocking(true).unwrap();
+  let url = format!("http://{}", listener.local_addr().unwrap());
+  let server = thread::spawn(move || {
+    let mut requests = Vec::new();
+
+    for reply in replies {
+      let Some(mut stream) = accept(&listener) else {
+        break;
+      };
+      requests.push(read_request(&mut stream));
+      thread::sleep(reply.delay);
+      let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reply.status,
+        reply.body.len()
+      );
+      for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+      }
+      head.push_str("\r\n");
+      // A client that timed out has closed the connection already.
+      let _ = stream
+        .write_all(head.as_bytes())
+        .and_then(|()| stream.write_all(&reply.body));
+    }
+
+    requests
+  });
+
+  (url, server)
+}
+
+/// A server answering every request with an empty `200 OK` over keep-alive connections, counting the connections opened and the requests in flight, to test the connection pool and the concurrency limit.
+///
+/// It stops accepting connections once dropped, the open ones end when the client closes them or stays idle for `ACCEPT_TIMEOUT`.
+pub(crate) struct KeepAlive {
+  pub(crate) url: String,
+  connections: Arc<AtomicUsize>,
+  most_in_flight: Arc<AtomicUsize>,
+  stop: Arc<AtomicBool>,
+  server: Option<thread::JoinHandle<()>>,
+}
+
+impl KeepAlive {
+  /// Start the server, answering each request `delay` after it came, so requests sent together are in flight together.
+  pub(crate) fn serve(delay: Duration) -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let most_in_flight = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (opened, most, stopped) = (
+      Arc::clone(&connections),
+      Arc::clone(&most_in_flight),
+      Arc::clone(&stop),
+    );
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let server = thread::spawn(move || {
+      while let Some(mut stream) = accept_until(&listener, &stopped) {
+        opened.fetch_add(1, Ordering::SeqCst);
+        let (most, in_flight) = (Arc::clone(&most), Arc::clone(&in_flight));
+        thread::spawn(move || {
+          let mut request = [0; 1024];
+          while matches!(stream.read(&mut request), Ok(read) if read > 0) {
+            let count = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(count, Ordering::SeqCst);
+            thread::sleep(delay);
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+            if stream.write_all(response.as_bytes()).is_err() {
+              break;
+            }
+          }
+        });
+      }
+    });
+
+    Self {
+      url,
+      connections,
+      most_in_flight,
+      stop,
+      server: Some(server),
+    }
+  }
+
+  /// The number of connections opened so far.
+  pub(crate) fn connections(&self) -> usize {
+    self.connections.load(Ordering::SeqCst)
+  }
+
+  /// The most requests the server was answering at once so far.
+  pub(crate) fn most_in_flight(&self) -> usize {
+    self.most_in_flight.load(Ordering::SeqCst)
+  }
+}
+
+impl Drop for KeepAlive {
+  fn drop(&mut self) {
+    self.stop.store(true, Ordering::SeqCst);
+    if let Some(server) = self.server.take() {
+      server.join().unwrap();
+    }
+  }
+}
+
+/// Wait for the next connection, `None` after `ACCEPT_TIMEOUT`.
+fn accept(listener: &TcpListener) -> Option<TcpStream> {
+  let started = Instant::now();
+
+  while started.elapsed() < ACCEPT_TIMEOUT {
+    match listener.accept() {
+      Ok((stream, _)) => return Some(blocking(stream)),
+      Err(error) if error.kind() == ErrorKind::WouldBlock => {
+        thread::sleep(Duration::from_millis(5));
+      }
+      Err(error) => panic!("{}", error),
+    }
+  }
+
+  None
+}
+
+/// Wait for the next connection, `None` once `stop` is set.
+fn accept_until(
+  listener: &TcpListener,
+  stop: &AtomicBool,
+) -> Option<TcpStream> {
+  while !stop.load(Ordering::SeqCst) {
+    match listener.accept() {
+      Ok((stream, _)) => return Some(blocking(stream)),
+      Err(error) if error.kind() == ErrorKind::WouldBlock => {
+        thread::sleep(Duration::from_millis(5));
+      }
+      Err(error) => panic!("{}", error),
+    }
+  }
+
+  None
+}
+
+/// Switch an accepted connection back to blocking reads, which give up after `ACCEPT_TIMEOUT`.
+fn blocking(stream: TcpStream) -> TcpStream {
+  stream.set_nonblocking(false).unwrap();
+  stream.set_read_timeout(Some(ACCEPT_TIMEOUT)).unwrap();
+  stream
+}
+
+/// Read a request up to the end of its body, as told by its `Content-Length`.
+fn read_request(stream: &mut TcpStream) -> String {
+  let mut request = Vec::new();
+  let mut chunk = [0; 4096];
+
+  loop {
+    let read = stream.read(&mut chunk).unwrap();
+    request.extend_from_slice(&chunk[..read]);
+    let text = String::from_utf8_lossy(&request);
+
+    if let Some(end) = text.find("\r\n\r\n") {
+      let length = text[..end]
+        .lines()
+        .find_map(|line| {
+          let (name, value) = line.split_once(':')?;
+          name
+            .eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+
+      if read == 0 || request.len() >= end + 4 + length {
+        return text.into_owned();
+      }
+    } else if read == 0 {
+      return text.into_owned();
+    }
+  }
+}
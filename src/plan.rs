@@ -0,0 +1,104 @@
+use crate::error::TinifyError;
+use crate::location::ResultUrl;
+use crate::Config;
+use crate::Operations;
+use crate::REQUEST_TIMEOUT;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// What compressing a source sends to Tinify, without the key or the url of the compressed image, which both grant access to it, to log or reproduce a compression.
+///
+/// Timeouts are in seconds. The crate doesn't retry on its own, so every request of the plan is sent once.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RequestPlan {
+  /// The endpoint the image is uploaded to, or Tinify is asked to download it from.
+  pub shrink_endpoint: String,
+
+  /// Whether the image was already uploaded, so only the operations are sent to its url, never the case in a dry run.
+  pub uploaded: bool,
+
+  /// The JSON body of the operations request, exactly as it is sent, `None` without operations.
+  pub operations: Option<String>,
+
+  /// The timeout of every request, except compressing from an url with a `source_fetch_timeout`.
+  pub request_timeout: f64,
+
+  /// The timeout of compressing from an url, which includes Tinify downloading the original.
+  pub source_fetch_timeout: Option<f64>,
+
+  /// Whether nothing is sent at all.
+  pub dry_run: bool,
+
+  /// Whether results are read from and written to a cache directory.
+  pub cache: bool,
+}
+
+impl RequestPlan {
+  pub(crate) fn new(
+    config: &Config,
+    result_url: Option<&ResultUrl>,
+    operations: &Operations,
+  ) -> Result<Self, TinifyError> {
+    let operations = if operations.is_empty() {
+      None
+    } else {
+      Some(serde_json::to_string(operations)?)
+    };
+
+    Ok(Self {
+      shrink_endpoint: config.endpoint()?.join("/shrink")?.to_string(),
+      uploaded: result_url.is_some(),
+      operations,
+      request_timeout: REQUEST_TIMEOUT.as_secs_f64(),
+      source_fetch_timeout: config
+        .source_fetch_timeout
+        .map(|timeout| timeout.as_secs_f64()),
+      dry_run: config.dry_run,
+      cache: config.cache.is_some(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::convert::Convert;
+  use crate::convert::Type;
+  use std::time::Duration;
+
+  #[test]
+  fn test_request_plan() -> Result<(), TinifyError> {
+    let config = Config {
+      source_fetch_timeout: Some(Duration::from_millis(1500)),
+      dry_run: true,
+      ..Config::default()
+    };
+    let mut operations = Operations::default();
+    let plan = RequestPlan::new(&config, None, &operations)?;
+
+    assert_eq!(plan.shrink_endpoint, "https://api.tinify.com/shrink");
+    assert!(!plan.uploaded);
+    assert_eq!(plan.operations, None);
+    assert_eq!(plan.source_fetch_timeout, Some(1.5));
+
+    operations.convert = Some(Convert {
+      r#type: vec![Type::Webp],
+    });
+    let result_url =
+      ResultUrl::parse("/output/2xnsp7jn34e5", &config.endpoint()?)?;
+    let plan = RequestPlan::new(&config, Some(&result_url), &operations)?;
+
+    assert_eq!(
+      serde_json::to_string(&plan)?,
+      concat!(
+        r#"{"shrink_endpoint":"https://api.tinify.com/shrink","#,
+        r#""uploaded":true,"#,
+        r#""operations":"{\"convert\":{\"type\":[\"image/webp\"]}}","#,
+        r#""request_timeout":300.0,"source_fetch_timeout":1.5,"#,
+        r#""dry_run":true,"cache":false}"#,
+      )
+    );
+
+    Ok(())
+  }
+}
@@ -0,0 +1,34 @@
+//! The commonly used types, for sync or async depending on the `async` feature.
+//!
+//! ```
+//! use tinify::prelude::*;
+//! ```
+
+pub use crate::account::AccountInfo;
+#[cfg(feature = "async")]
+pub use crate::async_bin::Client;
+#[cfg(feature = "async")]
+pub use crate::async_bin::Source;
+#[cfg(feature = "async")]
+pub use crate::async_bin::Tinify;
+pub use crate::convert::Convert;
+pub use crate::convert::Type;
+pub use crate::error::TinifyError;
+pub use crate::in_place::InPlace;
+pub use crate::in_place::InPlaceReport;
+pub use crate::location::ResultUrl;
+pub use crate::preserve::Metadata;
+pub use crate::preserve::Preserve;
+pub use crate::resize::Method;
+pub use crate::resize::Resize;
+pub use crate::store::Acl;
+pub use crate::store::Store;
+#[cfg(not(feature = "async"))]
+pub use crate::sync::Client;
+#[cfg(not(feature = "async"))]
+pub use crate::sync::Source;
+#[cfg(not(feature = "async"))]
+pub use crate::sync::Tinify;
+pub use crate::template::OutputTemplate;
+pub use crate::transform::Background;
+pub use crate::transform::Transform;
@@ -0,0 +1,30 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The metadata to copy from the uploaded image to the compressed one. The following metadata is available:
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Metadata {
+  /// Preserves any copyright information. This includes the EXIF copyright tag (JPEG), the XMP rights tag (PNG) as well as a Photoshop copyright flag or URL. Uses up to 90 additional bytes, plus the length of the copyright data.
+  #[serde(rename = "copyright")]
+  Copyright,
+
+  /// Preserves any creation date or time. This is the moment the image or photo was originally created. This includes the EXIF original date time tag (JPEG) or the XMP creation time (PNG). Uses around 70 additional bytes.
+  #[serde(rename = "creation")]
+  Creation,
+
+  /// Preserves any GPS location data that describes where the image or photo was taken. This includes the EXIF GPS latitude and GPS longitude tags (JPEG) or the XMP longitude and latitude (PNG). Uses around 130 additional bytes.
+  #[serde(rename = "location")]
+  Location,
+}
+
+/// # Preserving metadata
+///
+/// You can request that specific metadata is copied from the uploaded image to the compressed version. Preserving `copyright` information, the GPS `location` and the `creation` date are currently supported. Preserving metadata adds to the compressed file size, so you should only preserve metadata that is important to keep.
+///
+/// Preserving metadata will not count as an extra compression. Combined with a resize in the same request it is still one additional compression, for the resize.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Preserve {
+  /// A vector of `metadata`
+  pub metadata: Vec<Metadata>,
+}
@@ -2,13 +2,16 @@ use serde::Deserialize;
 use serde::Serialize;
 
 /// The method describes the way your image will be resized. The following methods are available:
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub enum Method {
   /// Scales the image down proportionally. You must provide either a target `width` or a target `height`, but not both. The scaled image will have exactly the provided width or height.
   #[serde(rename = "scale")]
   Scale,
 
   /// Scales the image down proportionally so that it fits within the given dimensions. You must provide both a `width` and a `height`. The scaled image will not exceed either of these dimensions.
+  ///
+  /// The given dimensions are a maximum, not the output size. Fitting into `400x200` yields `400x200` for an `800x400` image but `200x200` for a `600x600` image. Read the actual size with `Source::dimensions` after saving the result.
+  #[default]
   #[serde(rename = "fit")]
   Fit,
 
@@ -26,8 +29,12 @@ pub enum Method {
 ///
 /// You can also take advantage of intelligent cropping to create thumbnails that focus on the most visually important areas of your image.
 ///
+/// Images are never scaled up, a larger `width` or `height` gives an image of the original size. Reject such a resize before the request with `Tinify::reject_upscale`.
+///
 /// Resizing counts as one additional compression. For example, if you upload a single image and retrieve the optimized version plus 2 resized versions this will count as 3 compressions in total.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// The default is `Method::Fit` without a `width` or `height`, set the dimensions with `..Default::default()`. A resize whose dimensions don't match its method, like the default one, fails before the request.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Resize {
   pub method: Method,
 
@@ -37,3 +44,41 @@ pub struct Resize {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub height: Option<u32>,
 }
+
+impl Resize {
+  /// Fit the image within `max_width` by `max_height`, keeping its aspect ratio and never cropping it, unlike `Method::Cover` and `Method::Thumb`.
+  ///
+  /// Both dimensions are a maximum: the result is as large as possible within them, and exactly as large along one of them at most. Tinify never scales up, so an image that already fits keeps its size. Read the size actually produced with `Source::dimensions` once the operations ran.
+  pub fn fit_within(max_width: u32, max_height: u32) -> Self {
+    Self {
+      method: Method::Fit,
+      width: Some(max_width),
+      height: Some(max_height),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fit_within() -> Result<(), serde_json::Error> {
+    let resize = Resize::fit_within(400, 200);
+
+    assert_eq!(
+      resize,
+      Resize {
+        method: Method::Fit,
+        width: Some(400),
+        height: Some(200),
+      }
+    );
+    assert_eq!(
+      serde_json::to_string(&resize)?,
+      r#"{"method":"fit","width":400,"height":200}"#
+    );
+
+    Ok(())
+  }
+}
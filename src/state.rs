@@ -0,0 +1,24 @@
+//! The states of a `Source`, tracked in its type so an image is always chosen before it is compressed.
+//!
+//! A client only hands out sources that are [`Ready`], so `Source` alone always means `Source<Ready>`. The operations and the methods saving the image are only implemented for `Source<Ready>`, calling them on an [`Empty`] source doesn't compile.
+
+mod sealed {
+  pub trait Sealed {}
+}
+
+/// A state of a `Source`, implemented by [`Empty`] and [`Ready`] only.
+pub trait State: sealed::Sealed {}
+
+/// A source without an image yet, only the `from_*` methods of a client turn it into a [`Ready`] one.
+#[derive(Debug)]
+pub struct Empty;
+
+/// A source with its image chosen, ready for operations and to be saved.
+#[derive(Debug)]
+pub struct Ready;
+
+impl sealed::Sealed for Empty {}
+impl State for Empty {}
+
+impl sealed::Sealed for Ready {}
+impl State for Ready {}
@@ -0,0 +1,346 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// The cloud service a compressed image is stored in, with its credentials.
+#[derive(Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "service")]
+enum Service {
+  #[serde(rename = "s3")]
+  S3 {
+    aws_access_key_id: String,
+    aws_secret_access_key: String,
+    region: String,
+  },
+
+  #[serde(rename = "gcs")]
+  Gcs { gcp_access_token: String },
+}
+
+/// The access control list of an object stored in Amazon S3.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Acl {
+  /// Make the object publicly readable, what Tinify does by default.
+  #[serde(rename = "public-read")]
+  PublicRead,
+
+  /// Store the object without an ACL, so it gets the access of the bucket.
+  #[serde(rename = "no-acl")]
+  NoAcl,
+}
+
+/// # Saving to a cloud bucket
+///
+/// Tinify can save a compressed image directly to Amazon S3 or Google Cloud Storage, instead of it being downloaded. Pass it to `Source::store`, which returns the url of the stored image. The `path` is the bucket followed by the name of the object, like `example-bucket/my-images/optimized.jpg`.
+///
+/// Storing applies the pending operations and counts as one additional compression, like any of them. The credentials are only sent to Tinify with the request, they are left out of the `Debug` output.
+///
+/// An existing object at `path` is always replaced, there is no option to keep it. Tinify writes the object itself and its store API has no conditional put, and checking for the object first would need requests to S3 or GCS signed with the credentials, which this crate doesn't make.
+#[derive(Serialize, Clone, PartialEq, Eq)]
+pub struct Store {
+  #[serde(flatten)]
+  service: Service,
+
+  path: String,
+
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  headers: BTreeMap<String, String>,
+
+  #[serde(skip_serializing_if = "Option::is_none")]
+  acl: Option<Acl>,
+
+  #[serde(flatten)]
+  options: BTreeMap<String, Value>,
+}
+
+impl Store {
+  /// Store in an Amazon S3 bucket, with the access key of an IAM user allowed to put objects in it and the region of the bucket, like `us-west-1`.
+  pub fn s3(
+    access_key_id: &str,
+    secret_access_key: &str,
+    region: &str,
+    path: &str,
+  ) -> Self {
+    let service = Service::S3 {
+      aws_access_key_id: access_key_id.to_string(),
+      aws_secret_access_key: secret_access_key.to_string(),
+      region: region.to_string(),
+    };
+
+    Self::new(service, path)
+  }
+
+  /// Store in a Google Cloud Storage bucket, with an OAuth access token allowed to create objects in it.
+  pub fn gcs(access_token: &str, path: &str) -> Self {
+    let service = Service::Gcs {
+      gcp_access_token: access_token.to_string(),
+    };
+
+    Self::new(service, path)
+  }
+
+  fn new(service: Service, path: &str) -> Self {
+    Self {
+      service,
+      path: path.to_string(),
+      headers: BTreeMap::new(),
+      acl: None,
+      options: BTreeMap::new(),
+    }
+  }
+
+  /// Set a header of the stored object, like `Cache-Control`. Setting the same name again replaces its value.
+  ///
+  /// A line break in the name or the value is rejected with a `ClientError` whose error is `Store`.
+  pub fn header(
+    mut self,
+    name: &str,
+    value: &str,
+  ) -> Result<Self, TinifyError> {
+    if [name, value].iter().any(|part| part.contains(['\r', '\n'])) {
+      let upstream = Upstream {
+        error: "Store".to_string(),
+        message: format!(
+          "The `{}` header of the store request has a line break.",
+          name.escape_debug()
+        ),
+      };
+      return Err(TinifyError::ClientError { upstream });
+    }
+
+    self.headers.insert(name.to_string(), value.to_string());
+    Ok(self)
+  }
+
+  /// Let the stored object be cached publicly for `max_age`, as `Cache-Control: public, max-age=N` in whole seconds.
+  pub fn cache_control(mut self, max_age: Duration) -> Self {
+    self.headers.insert(
+      "Cache-Control".to_string(),
+      format!("public, max-age={}", max_age.as_secs()),
+    );
+    self
+  }
+
+  /// Have the stored object downloaded as a file called `name`, as `Content-Disposition: attachment`.
+  ///
+  /// Quotes and backslashes in `name` are escaped. A name that isn't ASCII is also sent percent-encoded as UTF-8, with the other characters replaced by `_` in the plain one.
+  pub fn content_disposition(self, name: &str) -> Result<Self, TinifyError> {
+    let plain: String = name
+      .chars()
+      .map(|c| match c {
+        '"' | '\\' => format!("\\{}", c),
+        c if c.is_ascii() => c.to_string(),
+        _ => "_".to_string(),
+      })
+      .collect();
+    let mut value = format!("attachment; filename=\"{}\"", plain);
+    if !name.is_ascii() {
+      value.push_str("; filename*=UTF-8''");
+      for byte in name.bytes() {
+        match byte {
+          b'a'..=b'z'
+          | b'A'..=b'Z'
+          | b'0'..=b'9'
+          | b'!'
+          | b'#'
+          | b'$'
+          | b'&'
+          | b'+'
+          | b'-'
+          | b'.'
+          | b'^'
+          | b'_'
+          | b'`'
+          | b'|'
+          | b'~' => value.push(char::from(byte)),
+          _ => value.push_str(&format!("%{:02X}", byte)),
+        }
+      }
+    }
+
+    self.header("Content-Disposition", &value)
+  }
+
+  /// Set the access control list of the stored object, S3 only.
+  pub fn acl(mut self, acl: Acl) -> Self {
+    self.acl = Some(acl);
+    self
+  }
+
+  /// Don't make the object publicly readable, S3 only. By default Tinify stores it with the `public-read` ACL.
+  pub fn no_acl(self) -> Self {
+    self.acl(Acl::NoAcl)
+  }
+
+  /// Add an option of the store request the crate has no typed method for yet, by its name in the API and its JSON value.
+  ///
+  /// Setting the same name again replaces its value. The names of the typed fields are rejected with a `ClientError` whose error is `Store`, like the `null` value.
+  pub fn option(
+    mut self,
+    name: &str,
+    value: Value,
+  ) -> Result<Self, TinifyError> {
+    let message = if matches!(
+      name,
+      "service"
+        | "aws_access_key_id"
+        | "aws_secret_access_key"
+        | "region"
+        | "gcp_access_token"
+        | "path"
+        | "headers"
+        | "acl"
+    ) {
+      format!(
+        "The `{}` field of the store request has its own method.",
+        name
+      )
+    } else if value.is_null() {
+      format!("The `{}` option of the store request has no value.", name)
+    } else {
+      self.options.insert(name.to_string(), value);
+      return Ok(self);
+    };
+    let upstream = Upstream {
+      error: "Store".to_string(),
+      message,
+    };
+
+    Err(TinifyError::ClientError { upstream })
+  }
+}
+
+/// The credentials are left out, a store can be logged with its source.
+impl fmt::Debug for Store {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut debug = f.debug_struct("Store");
+    match &self.service {
+      Service::S3 { region, .. } => {
+        debug.field("service", &"s3").field("region", region)
+      }
+      Service::Gcs { .. } => debug.field("service", &"gcs"),
+    };
+
+    debug
+      .field("path", &self.path)
+      .field("headers", &self.headers)
+      .field("acl", &self.acl)
+      .field("options", &self.options)
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+  use serde_json::json;
+
+  #[test]
+  fn test_serialize_store() -> Result<(), TinifyError> {
+    let s3 = Store::s3("AKIA", "s3cret", "us-west-1", "bucket/optimized.jpg")
+      .header("Cache-Control", "public, max-age=31536000")?
+      .no_acl();
+    let gcs = Store::gcs("ya29.token", "bucket/optimized.jpg")
+      .option("x-goog-meta", json!({"source": "tinify"}))?;
+
+    assert_eq!(
+      serde_json::to_value(&s3)?,
+      json!({
+        "service": "s3",
+        "aws_access_key_id": "AKIA",
+        "aws_secret_access_key": "s3cret",
+        "region": "us-west-1",
+        "path": "bucket/optimized.jpg",
+        "headers": {"Cache-Control": "public, max-age=31536000"},
+        "acl": "no-acl",
+      })
+    );
+    assert_eq!(
+      serde_json::to_value(&gcs)?,
+      json!({
+        "service": "gcs",
+        "gcp_access_token": "ya29.token",
+        "path": "bucket/optimized.jpg",
+        "x-goog-meta": {"source": "tinify"},
+      })
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_typed_headers() -> Result<(), TinifyError> {
+    let store =
+      Store::s3("AKIA", "s3cret", "us-west-1", "bucket/optimized.jpg")
+        .cache_control(Duration::from_secs(86400))
+        .content_disposition(r#"my "best" \ photo.jpg"#)?
+        .acl(Acl::PublicRead);
+    let unicode = Store::gcs("ya29.token", "bucket/optimized.jpg")
+      .content_disposition("café.jpg")?;
+
+    let value = serde_json::to_value(&store)?;
+    assert_eq!(
+      value["headers"],
+      json!({
+        "Cache-Control": "public, max-age=86400",
+        "Content-Disposition": r#"attachment; filename="my \"best\" \\ photo.jpg""#,
+      })
+    );
+    assert_eq!(value["acl"], "public-read");
+    assert_eq!(
+      serde_json::to_value(&unicode)?["headers"]["Content-Disposition"],
+      r#"attachment; filename="caf_.jpg"; filename*=UTF-8''caf%C3%A9.jpg"#
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_header_line_break() {
+    let store = Store::gcs("ya29.token", "bucket/optimized.jpg");
+
+    for result in [
+      store.clone().header("X-Meta", "a\r\nSet-Cookie: b"),
+      store.clone().header("X-Meta\n", "a"),
+      store.clone().content_disposition("photo\n.jpg"),
+    ] {
+      assert_matches!(
+        result,
+        Err(TinifyError::ClientError { ref upstream }) if upstream.error == "Store"
+      );
+    }
+  }
+
+  #[test]
+  fn test_store_option() {
+    let store = Store::gcs("ya29.token", "bucket/optimized.jpg");
+
+    for (name, value) in [
+      ("path", json!("bucket/other.jpg")),
+      ("service", json!("s3")),
+      ("custom", Value::Null),
+    ] {
+      assert_matches!(
+        store.clone().option(name, value),
+        Err(TinifyError::ClientError { ref upstream }) if upstream.error == "Store"
+      );
+    }
+  }
+
+  #[test]
+  fn test_debug_redacts_credentials() {
+    let s3 = Store::s3("AKIA", "s3cret", "us-west-1", "bucket/optimized.jpg");
+    let gcs = Store::gcs("ya29.token", "bucket/optimized.jpg");
+    let debug = format!("{:?} {:?}", s3, gcs);
+
+    assert!(debug.contains("us-west-1"), "{}", debug);
+    for secret in ["AKIA", "s3cret", "ya29.token"] {
+      assert!(!debug.contains(secret), "{}", debug);
+    }
+  }
+}
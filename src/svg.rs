@@ -0,0 +1,66 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use resvg::tiny_skia::Pixmap;
+use resvg::tiny_skia::Transform;
+use resvg::usvg::Options;
+use resvg::usvg::Tree;
+use resvg::usvg::TreeParsing;
+
+fn svg_error<E>(err: E) -> TinifyError
+where
+  E: ToString,
+{
+  let upstream = Upstream {
+    error: "Svg".to_string(),
+    message: err.to_string(),
+  };
+  TinifyError::ClientError { upstream }
+}
+
+/// Rasterize an SVG document to a PNG of exactly `width` by `height` pixels.
+pub(crate) fn rasterize(
+  svg: &[u8],
+  width: u32,
+  height: u32,
+) -> Result<Vec<u8>, TinifyError> {
+  let tree = Tree::from_data(svg, &Options::default()).map_err(svg_error)?;
+  let mut pixmap = Pixmap::new(width, height)
+    .ok_or_else(|| svg_error("The width and height must not be zero."))?;
+  let transform = Transform::from_scale(
+    width as f32 / tree.size.width(),
+    height as f32 / tree.size.height(),
+  );
+  resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+  pixmap.encode_png().map_err(svg_error)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+
+  const SVG: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="10" height="10" fill="red"/></svg>"#;
+
+  #[test]
+  fn test_rasterize_svg() -> Result<(), TinifyError> {
+    let png = rasterize(SVG, 40, 20)?;
+    let dim = imagesize::blob_size(&png).unwrap();
+
+    assert_eq!((dim.width, dim.height), (40, 20));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_rasterize_invalid_svg() {
+    assert_matches!(
+      rasterize(b"not an svg", 10, 10),
+      Err(TinifyError::ClientError { .. })
+    );
+    assert_matches!(
+      rasterize(SVG, 0, 10),
+      Err(TinifyError::ClientError { .. })
+    );
+  }
+}
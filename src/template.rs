@@ -0,0 +1,178 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+  Text(String),
+  Stem,
+  Ext,
+  Dir,
+  Counter,
+}
+
+/// An output path template for batch jobs, like `"{dir}/optimized/{stem}.{ext}"`.
+///
+/// The following placeholders are expanded for every input file:
+/// - `{stem}` the file name without its extension.
+/// - `{ext}` the extension without the dot, empty when there is none.
+/// - `{dir}` the parent directory, `.` when there is none.
+/// - `{n}` a counter, starting at 1 for the first input.
+///
+/// A template must contain `{stem}` or `{n}`, otherwise every input would be written to the same path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputTemplate {
+  template: String,
+  segments: Vec<Segment>,
+}
+
+impl OutputTemplate {
+  /// Parse and validate a template.
+  pub fn new(template: &str) -> Result<Self, TinifyError> {
+    let invalid = |message: String| {
+      let upstream = Upstream {
+        error: "Template".to_string(),
+        message,
+      };
+      TinifyError::ClientError { upstream }
+    };
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while !rest.is_empty() {
+      let next = rest.find(['{', '}']).unwrap_or(rest.len());
+      if next > 0 {
+        segments.push(Segment::Text(rest[..next].to_string()));
+        rest = &rest[next..];
+        continue;
+      }
+      if rest.starts_with('}') {
+        return Err(invalid(format!(
+          "Unmatched `}}` in output template `{}`.",
+          template
+        )));
+      }
+      let end = rest.find('}').ok_or_else(|| {
+        invalid(format!("Unclosed `{{` in output template `{}`.", template))
+      })?;
+      let segment = match &rest[1..end] {
+        "stem" => Segment::Stem,
+        "ext" => Segment::Ext,
+        "dir" => Segment::Dir,
+        "n" => Segment::Counter,
+        name => {
+          return Err(invalid(format!(
+            "Unknown placeholder `{{{}}}` in output template `{}`, expected `{{stem}}`, `{{ext}}`, `{{dir}}` or `{{n}}`.",
+            name, template
+          )))
+        }
+      };
+      segments.push(segment);
+      rest = &rest[end + 1..];
+    }
+
+    if !segments
+      .iter()
+      .any(|segment| matches!(segment, Segment::Stem | Segment::Counter))
+    {
+      return Err(invalid(format!(
+        "Output template `{}` must contain `{{stem}}` or `{{n}}`.",
+        template
+      )));
+    }
+
+    Ok(Self {
+      template: template.to_string(),
+      segments,
+    })
+  }
+
+  /// Expand the template for the `n`th input file, counting from 1.
+  pub fn expand<P>(&self, input: P, n: usize) -> PathBuf
+  where
+    P: AsRef<Path>,
+  {
+    let input = input.as_ref();
+    let lossy = |part: Option<&std::ffi::OsStr>| {
+      part.map(|part| part.to_string_lossy().into_owned())
+    };
+    let dir = match input.parent() {
+      Some(parent) if !parent.as_os_str().is_empty() => {
+        parent.to_string_lossy()
+      }
+      _ => ".".into(),
+    };
+    let mut path = String::new();
+
+    for segment in &self.segments {
+      match segment {
+        Segment::Text(text) => path.push_str(text),
+        Segment::Stem => {
+          path.push_str(&lossy(input.file_stem()).unwrap_or_default())
+        }
+        Segment::Ext => {
+          path.push_str(&lossy(input.extension()).unwrap_or_default())
+        }
+        Segment::Dir => path.push_str(&dir),
+        Segment::Counter => path.push_str(&n.to_string()),
+      }
+    }
+
+    PathBuf::from(path)
+  }
+}
+
+impl fmt::Display for OutputTemplate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.template)
+  }
+}
+
+impl FromStr for OutputTemplate {
+  type Err = TinifyError;
+
+  fn from_str(template: &str) -> Result<Self, Self::Err> {
+    OutputTemplate::new(template)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+
+  #[test]
+  fn test_expand_template() -> Result<(), TinifyError> {
+    let template = OutputTemplate::new("{dir}/optimized/{stem}.{ext}")?;
+
+    assert_eq!(
+      template.expand("images/panda.png", 1),
+      Path::new("images/optimized/panda.png")
+    );
+    assert_eq!(
+      template.expand("panda.png", 1),
+      Path::new("./optimized/panda.png")
+    );
+    assert_eq!(
+      "out/{n}-{stem}.webp"
+        .parse::<OutputTemplate>()?
+        .expand("a/b", 3),
+      Path::new("out/3-b.webp")
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_invalid_template() {
+    for template in ["", "{dir}/out.png", "{stem", "{stem}}", "{name}.png"] {
+      assert_matches!(
+        OutputTemplate::new(template),
+        Err(TinifyError::ClientError { .. })
+      );
+    }
+  }
+}
@@ -1,12 +1,154 @@
+use crate::error::TinifyError;
+use crate::error::Upstream;
+use serde::de;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use serde::Serializer;
+use std::fmt;
+use std::str::FromStr;
+
+/// The background color used to fill a transparent image. The following colors are available:
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+  /// The named color `white`.
+  White,
+
+  /// The named color `black`.
+  Black,
+
+  /// A custom color, serialized as its hex value `#RRGGBB`.
+  Rgb(u8, u8, u8),
+}
+
+impl Background {
+  /// Create a custom background color from its red, green and blue components.
+  pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+    Background::Rgb(red, green, blue)
+  }
+
+  /// Parse a background color from a hex value like `#800020`.
+  pub fn hex(value: &str) -> Result<Self, TinifyError> {
+    let invalid = || {
+      let upstream = Upstream {
+        error: "Background".to_string(),
+        message: format!(
+          "Invalid background color `{}`, expected a hex value like `#000000`.",
+          value
+        ),
+      };
+      TinifyError::ClientError { upstream }
+    };
+    let digits = value.strip_prefix('#').ok_or_else(invalid)?;
+
+    if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+      return Err(invalid());
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16);
+    match (channel(0), channel(2), channel(4)) {
+      (Ok(red), Ok(green), Ok(blue)) => Ok(Background::Rgb(red, green, blue)),
+      _ => Err(invalid()),
+    }
+  }
+}
+
+impl fmt::Display for Background {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      Background::White => write!(f, "white"),
+      Background::Black => write!(f, "black"),
+      Background::Rgb(red, green, blue) => {
+        write!(f, "#{:02X}{:02X}{:02X}", red, green, blue)
+      }
+    }
+  }
+}
+
+impl FromStr for Background {
+  type Err = TinifyError;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "white" => Ok(Background::White),
+      "black" => Ok(Background::Black),
+      _ => Background::hex(value),
+    }
+  }
+}
+
+impl Serialize for Background {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for Background {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(de::Error::custom)
+  }
+}
 
 /// The transform object specifies the stylistic transformations that will be applied to your image. Include a `background property` to fill a transparent image's background. The following options are available to specify a background color:
-/// - A hex value. Custom background color using the color's hex value: `#000000`.
+/// - A hex value. Custom background color using the color's hex value: `#000000`, see [`Background::rgb`] and [`Background::hex`].
 /// - `white` or `black`. Only the colors white and black are supported as strings.
 ///
-/// You must specify a background color if you wish to convert an image with a transparent background to an image type which does not support transparency (like JPEG).
-#[derive(Serialize, Deserialize, Debug)]
+/// You must specify a background color if you wish to convert an image with a transparent background to an image type which does not support transparency (like JPEG). Converting a transparent PNG or WebP to JPEG only without a transform fails before the request, with a `ClientError` whose error is `Transparency`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Transform {
-  pub background: String,
+  pub background: Background,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use assert_matches::assert_matches;
+
+  #[test]
+  fn test_serialize_background() -> Result<(), TinifyError> {
+    let transform = Transform {
+      background: Background::rgb(128, 0, 32),
+    };
+
+    assert_eq!(
+      serde_json::to_string(&transform)?,
+      r##"{"background":"#800020"}"##
+    );
+    assert_eq!(serde_json::to_string(&Background::White)?, r#""white""#);
+    assert_eq!(serde_json::to_string(&Background::Black)?, r#""black""#);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_parse_background() -> Result<(), TinifyError> {
+    assert_eq!("white".parse::<Background>()?, Background::White);
+    assert_eq!(
+      "#800020".parse::<Background>()?,
+      Background::rgb(128, 0, 32)
+    );
+    assert_eq!(Background::hex("#ffffff")?, Background::rgb(255, 255, 255));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_invalid_background() {
+    for value in ["", "red", "800020", "#80002", "#80002G", "#8000200"] {
+      assert_matches!(
+        value.parse::<Background>(),
+        Err(TinifyError::ClientError { .. })
+      );
+    }
+    assert!(
+      serde_json::from_str::<Transform>(r#"{"background":"red"}"#).is_err()
+    );
+  }
 }